@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+// Packagers replace the embedded alert with PROMPTY_AUDIO, relative paths start at the crate root.
+fn main() {
+    let root = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").unwrap());
+    let audio = match std::env::var_os("PROMPTY_AUDIO") {
+        Some(path) => root.join(path),
+        None => root.join("audio.mp3"),
+    };
+    println!("cargo:rerun-if-env-changed=PROMPTY_AUDIO");
+    println!("cargo:rerun-if-changed={}", audio.display());
+    println!("cargo:rustc-env=PROMPTY_AUDIO_PATH={}", audio.display());
+}
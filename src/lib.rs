@@ -0,0 +1,1491 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+mod sun;
+
+pub use sun::{compute_sunrise, compute_sunset};
+
+pub const MIN_WAKEUP_TIME: &str = "6:30";
+pub const MAX_WAKEUP_TIME: &str = "8:22";
+pub const SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES: i64 = 15;
+pub const LUNCH_TIME_SINCE_SUNRISE_IN_HOURS: f32 = 5.5;
+pub const SOLAR_NOON_SINCE_SUNRISE_IN_HOURS: f32 = 6.0;
+pub const DINNER_TIME_SINCE_SUNRISE_IN_HOURS: f32 = 11.5;
+pub const SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS: f32 = 15.5;
+pub const SCREENS_OFF_BEFORE_BED_IN_MINUTES: i64 = 60;
+pub const GOLDEN_HOUR_BEFORE_SUNSET_IN_MINUTES: i64 = 60;
+pub const ALERT_LEAD_IN_MINUTES: i64 = 10;
+pub const SNOOZE_MINUTES: i64 = 9;
+pub const SNOOZE_WINDOW_IN_SECONDS: u64 = 10;
+pub const ALERT_FADE_OUT_IN_SECONDS: f32 = 1.5;
+pub const MIN_EVENT_GAP_IN_MINUTES: i64 = 2;
+pub const DEFAULT_PROFILE: &str = "default";
+pub const SUNRISE_FORMATS: [&str; 5] = ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M%p", "%I:%M:%S %p"];
+
+pub trait Clock {
+    fn now(&self) -> NaiveTime;
+    fn now_date_time(&self) -> NaiveDateTime {
+        Local::now().date_naive().and_time(self.now())
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Zone(pub Option<chrono_tz::Tz>);
+
+impl Zone {
+    pub fn now(&self) -> NaiveDateTime {
+        self.from_utc(Utc::now())
+    }
+    pub fn from_utc(&self, utc: DateTime<Utc>) -> NaiveDateTime {
+        match self.0 {
+            Some(tz) => utc.with_timezone(&tz).naive_local(),
+            None => utc.with_timezone(&Local).naive_local(),
+        }
+    }
+    /// `None` for wall clock times skipped when the clocks spring forward.
+    pub fn to_utc(&self, local: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self.0 {
+            Some(tz) => tz
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|t| t.to_utc()),
+            None => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|t| t.to_utc()),
+        }
+    }
+}
+
+pub struct SystemClock(pub Zone);
+
+impl Clock for SystemClock {
+    fn now(&self) -> NaiveTime {
+        self.0.now().time()
+    }
+    fn now_date_time(&self) -> NaiveDateTime {
+        self.0.now()
+    }
+}
+
+pub struct FixedClock(pub NaiveTime);
+
+impl Clock for FixedClock {
+    fn now(&self) -> NaiveTime {
+        self.0
+    }
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Anchor {
+    #[default]
+    Sunrise,
+    Sunset,
+    Bed,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct EventConfig {
+    pub label: String,
+    #[serde(alias = "offset_from_sunrise_in_hours")]
+    pub offset_in_hours: f32,
+    #[serde(default)]
+    pub anchor: Anchor,
+    pub alert_lead_in_minutes: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct Config {
+    pub min_wakeup_time: String,
+    pub max_wakeup_time: String,
+    pub sunrise_modifier_for_wake_up_time_in_minutes: i64,
+    pub weekend_wake_up_delay_in_minutes: i64,
+    pub lunch_time_since_sunrise_in_hours: f32,
+    pub solar_noon_since_sunrise_in_hours: f32,
+    pub dinner_time_since_sunrise_in_hours: f32,
+    pub sunrise_modifier_for_bed_time_in_hours: f32,
+    pub bed_time_since_sunset_in_hours: Option<f32>,
+    pub screens_off_before_bed_in_minutes: i64,
+    pub golden_hour_before_sunset_in_minutes: i64,
+    pub golden_hour_since_sunrise_in_hours: Option<f32>,
+    pub alert_lead_in_minutes: i64,
+    pub alert_leads_in_minutes: Option<Vec<i64>>,
+    pub alert_lead_in_minutes_per_event: BTreeMap<String, i64>,
+    pub snooze_minutes: i64,
+    pub snooze_window_in_seconds: u64,
+    pub alert_fade_out_in_seconds: f32,
+    pub min_event_gap_in_minutes: i64,
+    pub labels: BTreeMap<String, String>,
+    pub enabled: BTreeMap<String, bool>,
+    pub sound: Option<PathBuf>,
+    pub sounds: BTreeMap<String, PathBuf>,
+    pub volume: f32,
+    pub twelve_hour_clock: bool,
+    pub events: Vec<EventConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            min_wakeup_time: String::from(MIN_WAKEUP_TIME),
+            max_wakeup_time: String::from(MAX_WAKEUP_TIME),
+            sunrise_modifier_for_wake_up_time_in_minutes:
+                SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES,
+            weekend_wake_up_delay_in_minutes: 0,
+            lunch_time_since_sunrise_in_hours: LUNCH_TIME_SINCE_SUNRISE_IN_HOURS,
+            solar_noon_since_sunrise_in_hours: SOLAR_NOON_SINCE_SUNRISE_IN_HOURS,
+            dinner_time_since_sunrise_in_hours: DINNER_TIME_SINCE_SUNRISE_IN_HOURS,
+            sunrise_modifier_for_bed_time_in_hours: SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS,
+            bed_time_since_sunset_in_hours: None,
+            screens_off_before_bed_in_minutes: SCREENS_OFF_BEFORE_BED_IN_MINUTES,
+            golden_hour_before_sunset_in_minutes: GOLDEN_HOUR_BEFORE_SUNSET_IN_MINUTES,
+            golden_hour_since_sunrise_in_hours: None,
+            alert_lead_in_minutes: ALERT_LEAD_IN_MINUTES,
+            alert_leads_in_minutes: None,
+            alert_lead_in_minutes_per_event: BTreeMap::new(),
+            snooze_minutes: SNOOZE_MINUTES,
+            snooze_window_in_seconds: SNOOZE_WINDOW_IN_SECONDS,
+            alert_fade_out_in_seconds: ALERT_FADE_OUT_IN_SECONDS,
+            min_event_gap_in_minutes: MIN_EVENT_GAP_IN_MINUTES,
+            labels: BTreeMap::new(),
+            enabled: BTreeMap::new(),
+            sound: None,
+            sounds: BTreeMap::new(),
+            volume: 1.0,
+            twelve_hour_clock: false,
+            events: Vec::new(),
+        }
+    }
+}
+
+pub enum ConfigError {
+    Unreadable(PathBuf, std::io::Error),
+    Invalid(PathBuf, toml::de::Error),
+    UnknownProfile(String, Vec<String>),
+    InvalidTime(PathBuf, &'static str, String),
+    NoEnabledEvents,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Unreadable(path, e) => {
+                write!(f, "Could not read config {}: {}", path.display(), e)
+            }
+            ConfigError::Invalid(path, e) => {
+                write!(f, "Invalid config {}: {}", path.display(), e)
+            }
+            ConfigError::UnknownProfile(name, available) => write!(
+                f,
+                "There is no profile called '{}', available are: {}",
+                name,
+                available.join(", ")
+            ),
+            ConfigError::InvalidTime(path, key, value) => write!(
+                f,
+                "Invalid config {}: {} = \"{}\" is not a time like 6:30 or 6:30 AM",
+                path.display(),
+                key,
+                value
+            ),
+            ConfigError::NoEnabledEvents => {
+                write!(f, "Every event is disabled in the [enabled] table")
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("prompty").join("config.toml"))
+    }
+    pub fn load(profile: &str) -> Result<Config, ConfigError> {
+        match Config::path() {
+            Some(path) if path.exists() => {
+                let content = std::fs::read_to_string(&path)
+                    .map_err(|e| ConfigError::Unreadable(path.clone(), e))?;
+                let config = Config::parse_profile(&content, profile)
+                    .map_err(|e| ConfigError::Invalid(path.clone(), e))?
+                    .ok_or_else(|| {
+                        ConfigError::UnknownProfile(
+                            profile.to_string(),
+                            Config::profile_names(&content),
+                        )
+                    })?;
+                for (key, value) in [
+                    ("min_wakeup_time", &config.min_wakeup_time),
+                    ("max_wakeup_time", &config.max_wakeup_time),
+                ] {
+                    if parse_config_time(value).is_none() {
+                        return Err(ConfigError::InvalidTime(path, key, value.clone()));
+                    }
+                }
+                Ok(config)
+            }
+            _ if profile == DEFAULT_PROFILE => Ok(Config::default()),
+            _ => Err(ConfigError::UnknownProfile(
+                profile.to_string(),
+                vec![String::from(DEFAULT_PROFILE)],
+            )),
+        }
+    }
+    pub fn parse(content: &str) -> Result<Config, toml::de::Error> {
+        toml::from_str(content)
+    }
+    /// Keys in `[profiles.NAME]` override the top level ones, `None` if there is no such profile.
+    pub fn parse_profile(content: &str, profile: &str) -> Result<Option<Config>, toml::de::Error> {
+        let mut table: toml::Table = toml::from_str(content)?;
+        let overrides = match table.remove("profiles") {
+            Some(toml::Value::Table(mut profiles)) => profiles.remove(profile),
+            _ => None,
+        };
+        match overrides {
+            Some(toml::Value::Table(overrides)) => table.extend(overrides),
+            // Without its own table the default profile is just the top level.
+            None if profile == DEFAULT_PROFILE => {}
+            _ => return Ok(None),
+        }
+        toml::Value::Table(table).try_into().map(Some)
+    }
+    pub fn profile_names(content: &str) -> Vec<String> {
+        let mut names = vec![String::from(DEFAULT_PROFILE)];
+        if let Ok(table) = toml::from_str::<toml::Table>(content) {
+            if let Some(toml::Value::Table(profiles)) = table.get("profiles") {
+                names.extend(
+                    profiles
+                        .keys()
+                        .filter(|name| *name != DEFAULT_PROFILE)
+                        .cloned(),
+                );
+            }
+        }
+        names
+    }
+    pub fn uses_sunset(&self) -> bool {
+        self.bed_time_since_sunset_in_hours.is_some()
+            || self
+                .events
+                .iter()
+                .any(|event| event.anchor == Anchor::Sunset)
+    }
+}
+
+/// Which event a `TimestampType` is, regardless of its time.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EventId {
+    WakeUpTime,
+    BedTime,
+    LunchTime,
+    SolarNoon,
+    DinnerTime,
+    ScreensOff,
+    GoldenHour,
+    Custom(String),
+}
+
+#[derive(PartialEq, Clone)]
+pub enum TimestampType {
+    WakeUpTime(NaiveTime),
+    BedTime(NaiveTime),
+    LunchTime(NaiveTime),
+    SolarNoon(NaiveTime),
+    DinnerTime(NaiveTime),
+    ScreensOff(NaiveTime),
+    GoldenHour(NaiveTime),
+    Custom(String, NaiveTime),
+}
+
+impl std::fmt::Debug for TimestampType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TimestampType::WakeUpTime(_) => write!(f, "Wake up time"),
+            TimestampType::LunchTime(_) => write!(f, "Optimal lunch time"),
+            TimestampType::SolarNoon(_) => write!(f, "Solar noon"),
+            TimestampType::DinnerTime(_) => {
+                write!(f, "Optimal evening dinner time")
+            }
+            TimestampType::ScreensOff(_) => write!(f, "Screens off time"),
+            TimestampType::GoldenHour(_) => write!(f, "Golden hour"),
+            TimestampType::BedTime(_) => write!(f, "Bedtime"),
+            TimestampType::Custom(label, _) => write!(f, "{}", label),
+        }
+    }
+}
+
+impl TimestampType {
+    pub fn get_naive_time(&self) -> NaiveTime {
+        match self {
+            TimestampType::WakeUpTime(v)
+            | TimestampType::BedTime(v)
+            | TimestampType::LunchTime(v)
+            | TimestampType::SolarNoon(v)
+            | TimestampType::DinnerTime(v)
+            | TimestampType::ScreensOff(v)
+            | TimestampType::GoldenHour(v)
+            | TimestampType::Custom(_, v) => *v,
+        }
+    }
+    pub fn get_name(&self) -> &str {
+        match self {
+            TimestampType::WakeUpTime(_) => "wake",
+            TimestampType::LunchTime(_) => "lunch",
+            TimestampType::SolarNoon(_) => "noon",
+            TimestampType::DinnerTime(_) => "dinner",
+            TimestampType::ScreensOff(_) => "screens",
+            TimestampType::GoldenHour(_) => "golden",
+            TimestampType::BedTime(_) => "bed",
+            TimestampType::Custom(label, _) => label,
+        }
+    }
+    pub fn get_id(&self) -> EventId {
+        match self {
+            TimestampType::WakeUpTime(_) => EventId::WakeUpTime,
+            TimestampType::LunchTime(_) => EventId::LunchTime,
+            TimestampType::SolarNoon(_) => EventId::SolarNoon,
+            TimestampType::DinnerTime(_) => EventId::DinnerTime,
+            TimestampType::ScreensOff(_) => EventId::ScreensOff,
+            TimestampType::BedTime(_) => EventId::BedTime,
+            TimestampType::GoldenHour(_) => EventId::GoldenHour,
+            TimestampType::Custom(label, _) => EventId::Custom(label.clone()),
+        }
+    }
+    /// The same event at another time.
+    pub fn with_time(&self, time: NaiveTime) -> TimestampType {
+        match self {
+            TimestampType::WakeUpTime(_) => TimestampType::WakeUpTime(time),
+            TimestampType::LunchTime(_) => TimestampType::LunchTime(time),
+            TimestampType::SolarNoon(_) => TimestampType::SolarNoon(time),
+            TimestampType::DinnerTime(_) => TimestampType::DinnerTime(time),
+            TimestampType::ScreensOff(_) => TimestampType::ScreensOff(time),
+            TimestampType::BedTime(_) => TimestampType::BedTime(time),
+            TimestampType::GoldenHour(_) => TimestampType::GoldenHour(time),
+            TimestampType::Custom(label, _) => TimestampType::Custom(label.clone(), time),
+        }
+    }
+    pub fn get_summary_label(&self) -> &str {
+        match self {
+            TimestampType::WakeUpTime(_) => "Wake up time",
+            TimestampType::LunchTime(_) => "Lunch",
+            TimestampType::SolarNoon(_) => "Solar noon",
+            TimestampType::DinnerTime(_) => "Evening dinner",
+            TimestampType::ScreensOff(_) => "Screens off",
+            TimestampType::GoldenHour(_) => "Golden hour",
+            TimestampType::BedTime(_) => "Bed time",
+            TimestampType::Custom(label, _) => label,
+        }
+    }
+}
+
+fn parse_config_time(value: &str) -> Option<NaiveTime> {
+    SUNRISE_FORMATS
+        .iter()
+        .find_map(|time_format| NaiveTime::parse_from_str(value, time_format).ok())
+}
+
+// Returns the shifted time and how many days it wrapped past midnight.
+fn shift(time: NaiveTime, duration: chrono::Duration) -> (NaiveTime, i64) {
+    let (time, wrapped_seconds) = time.overflowing_add_signed(duration);
+    (time, wrapped_seconds / 86400)
+}
+
+fn hours(hours: f32) -> chrono::Duration {
+    chrono::Duration::seconds((hours * 3600.0) as i64)
+}
+
+/// Assembles a schedule without a config file, offsets default to the compiled ones.
+/// Wake up, lunch, solar noon, dinner and bed offsets are relative to the sunrise.
+pub struct TimestampsBuilder {
+    sunrise: NaiveTime,
+    measured_sunrise: Option<NaiveTime>,
+    sunset: Option<NaiveTime>,
+    date: Option<NaiveDate>,
+    zone: Zone,
+    wake_offset: chrono::Duration,
+    weekend_wake_delay: chrono::Duration,
+    lunch_offset: chrono::Duration,
+    solar_noon_offset: chrono::Duration,
+    dinner_offset: chrono::Duration,
+    bed_offset: chrono::Duration,
+    bed_offset_from_sunset: Option<chrono::Duration>,
+    screens_off_offset: chrono::Duration,
+    golden_hour_before_sunset: chrono::Duration,
+    golden_hour_offset: Option<chrono::Duration>,
+    events: Vec<(String, Anchor, chrono::Duration)>,
+    alert_leads: BTreeMap<String, chrono::Duration>,
+    labels: BTreeMap<String, String>,
+    enabled: BTreeMap<String, bool>,
+}
+
+impl Default for TimestampsBuilder {
+    fn default() -> TimestampsBuilder {
+        TimestampsBuilder::from_config(&Config::default())
+    }
+}
+
+impl TimestampsBuilder {
+    pub fn new() -> TimestampsBuilder {
+        TimestampsBuilder::default()
+    }
+    pub fn from_config(config: &Config) -> TimestampsBuilder {
+        TimestampsBuilder {
+            // Loading the config already rejected times that do not parse.
+            sunrise: parse_config_time(&config.min_wakeup_time).unwrap_or(NaiveTime::MIN),
+            measured_sunrise: None,
+            sunset: None,
+            date: None,
+            zone: Zone::default(),
+            wake_offset: -chrono::Duration::minutes(
+                config.sunrise_modifier_for_wake_up_time_in_minutes,
+            ),
+            weekend_wake_delay: chrono::Duration::minutes(config.weekend_wake_up_delay_in_minutes),
+            lunch_offset: hours(config.lunch_time_since_sunrise_in_hours),
+            solar_noon_offset: hours(config.solar_noon_since_sunrise_in_hours),
+            dinner_offset: hours(config.dinner_time_since_sunrise_in_hours),
+            bed_offset: hours(config.sunrise_modifier_for_bed_time_in_hours),
+            bed_offset_from_sunset: config.bed_time_since_sunset_in_hours.map(hours),
+            screens_off_offset: -chrono::Duration::minutes(
+                config.screens_off_before_bed_in_minutes,
+            ),
+            golden_hour_before_sunset: chrono::Duration::minutes(
+                config.golden_hour_before_sunset_in_minutes,
+            ),
+            golden_hour_offset: config.golden_hour_since_sunrise_in_hours.map(hours),
+            events: config
+                .events
+                .iter()
+                .map(|event| {
+                    (
+                        event.label.clone(),
+                        event.anchor,
+                        hours(event.offset_in_hours),
+                    )
+                })
+                .collect(),
+            alert_leads: config
+                .alert_lead_in_minutes_per_event
+                .iter()
+                .map(|(name, minutes)| (name.to_lowercase(), chrono::Duration::minutes(*minutes)))
+                .chain(config.events.iter().filter_map(|event| {
+                    event.alert_lead_in_minutes.map(|minutes| {
+                        (
+                            event.label.to_lowercase(),
+                            chrono::Duration::minutes(minutes),
+                        )
+                    })
+                }))
+                .collect(),
+            labels: config
+                .labels
+                .iter()
+                .map(|(name, text)| (name.to_lowercase(), text.clone()))
+                .collect(),
+            enabled: config
+                .enabled
+                .iter()
+                .map(|(name, enabled)| (name.to_lowercase(), *enabled))
+                .collect(),
+        }
+    }
+    pub fn sunrise(mut self, sunrise: NaiveTime) -> TimestampsBuilder {
+        self.sunrise = sunrise;
+        self
+    }
+    /// The sunrise before it was clamped, solar noon lies halfway from it to the sunset.
+    pub fn measured_sunrise(mut self, sunrise: NaiveTime) -> TimestampsBuilder {
+        self.measured_sunrise = Some(sunrise);
+        self
+    }
+    pub fn sunset(mut self, sunset: Option<NaiveTime>) -> TimestampsBuilder {
+        self.sunset = sunset;
+        self
+    }
+    /// Pins the schedule to one day instead of whichever day it is.
+    pub fn date(mut self, date: Option<NaiveDate>) -> TimestampsBuilder {
+        self.date = date;
+        self
+    }
+    /// The zone whose daylight saving time changes the offsets account for.
+    pub fn zone(mut self, zone: Zone) -> TimestampsBuilder {
+        self.zone = zone;
+        self
+    }
+    pub fn wake_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.wake_offset = offset;
+        self
+    }
+    /// Added to the wake up offset on Saturdays and Sundays.
+    pub fn weekend_wake_delay(mut self, delay: chrono::Duration) -> TimestampsBuilder {
+        self.weekend_wake_delay = delay;
+        self
+    }
+    pub fn lunch_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.lunch_offset = offset;
+        self
+    }
+    /// Only used without a sunset, otherwise solar noon is halfway to it.
+    pub fn solar_noon_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.solar_noon_offset = offset;
+        self
+    }
+    pub fn dinner_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.dinner_offset = offset;
+        self
+    }
+    pub fn bed_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.bed_offset = offset;
+        self.bed_offset_from_sunset = None;
+        self
+    }
+    pub fn bed_offset_from_sunset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.bed_offset_from_sunset = Some(offset);
+        self
+    }
+    /// Relative to bed time, so negative values fall before it.
+    pub fn screens_off_offset(mut self, offset: chrono::Duration) -> TimestampsBuilder {
+        self.screens_off_offset = offset;
+        self
+    }
+    /// How long before the sunset the golden hour starts.
+    pub fn golden_hour_before_sunset(mut self, span: chrono::Duration) -> TimestampsBuilder {
+        self.golden_hour_before_sunset = span;
+        self
+    }
+    /// Places the golden hour relative to the sunrise when the sunset is unknown,
+    /// without either there is no golden hour.
+    pub fn golden_hour_offset(mut self, offset: Option<chrono::Duration>) -> TimestampsBuilder {
+        self.golden_hour_offset = offset;
+        self
+    }
+    pub fn event(
+        mut self,
+        label: &str,
+        anchor: Anchor,
+        offset: chrono::Duration,
+    ) -> TimestampsBuilder {
+        self.events.push((label.to_string(), anchor, offset));
+        self
+    }
+    // Shifts on the zoned clock, so an offset across a daylight saving time change
+    // still spans that many real hours and lands on the wall clock time they end at.
+    fn shift_on(&self, day: i64, time: NaiveTime, duration: chrono::Duration) -> (NaiveTime, i64) {
+        let date =
+            self.date.unwrap_or_else(|| self.zone.now().date()) + chrono::Duration::days(day);
+        match self.zone.to_utc(date.and_time(time)) {
+            Some(utc) => {
+                let shifted = self.zone.from_utc(utc + duration);
+                (shifted.time(), day + (shifted.date() - date).num_days())
+            }
+            None => {
+                let (time, wrapped) = shift(time, duration);
+                (time, day + wrapped)
+            }
+        }
+    }
+    /// Replaces the global alert leads for the event called `name` (wake, lunch, ... or a label).
+    pub fn alert_lead(mut self, name: &str, lead: chrono::Duration) -> TimestampsBuilder {
+        self.alert_leads.insert(name.to_lowercase(), lead);
+        self
+    }
+    /// Shows `text` instead of the built in label for the event called `name`.
+    pub fn label(mut self, name: &str, text: &str) -> TimestampsBuilder {
+        self.labels.insert(name.to_lowercase(), text.to_string());
+        self
+    }
+    /// Leaves the event called `name` out of the schedule when `enabled` is false.
+    pub fn enabled(mut self, name: &str, enabled: bool) -> TimestampsBuilder {
+        self.enabled.insert(name.to_lowercase(), enabled);
+        self
+    }
+    pub fn build(self) -> Timestamps {
+        let sunrise = self.sunrise;
+        // The clamp only concerns waking up, the sun is still halfway across at solar noon.
+        let measured_sunrise = self.measured_sunrise.unwrap_or(sunrise);
+        let sunset = self.sunset;
+        let mut sunset_anchored = BTreeSet::new();
+        let mut bed_anchored = BTreeSet::new();
+        let (bed_anchor, bed_offset) = match (sunset, self.bed_offset_from_sunset) {
+            (Some(sunset), Some(offset)) => (sunset, offset),
+            _ => (sunrise, self.bed_offset),
+        };
+        let (bed_time, bed_day) = self.shift_on(0, bed_anchor, bed_offset);
+        // Derived from bed time so it follows whichever anchor bed time uses, shifted
+        // from that anchor in one go since a repeated hour makes bed time ambiguous.
+        let (screens_off, screens_off_day) =
+            self.shift_on(0, bed_anchor, bed_offset + self.screens_off_offset);
+        let bed_time = TimestampType::BedTime(bed_time);
+        if sunset.is_some() && self.bed_offset_from_sunset.is_some() {
+            sunset_anchored.insert(bed_time.get_id());
+        }
+        let wake_offset = match self
+            .date
+            .unwrap_or_else(|| self.zone.now().date())
+            .weekday()
+        {
+            chrono::Weekday::Sat | chrono::Weekday::Sun => {
+                self.wake_offset + self.weekend_wake_delay
+            }
+            _ => self.wake_offset,
+        };
+        let (wake_up_time, wake_up_day) = self.shift_on(0, sunrise, wake_offset);
+        let (lunch_time, lunch_day) = self.shift_on(0, sunrise, self.lunch_offset);
+        let (solar_noon, solar_noon_day) = match sunset {
+            Some(sunset) => shift(
+                measured_sunrise,
+                sunset.signed_duration_since(measured_sunrise) / 2,
+            ),
+            None => self.shift_on(0, sunrise, self.solar_noon_offset),
+        };
+        let (dinner_time, dinner_day) = self.shift_on(0, sunrise, self.dinner_offset);
+        let mut events = vec![
+            (TimestampType::WakeUpTime(wake_up_time), wake_up_day),
+            (TimestampType::LunchTime(lunch_time), lunch_day),
+            (TimestampType::SolarNoon(solar_noon), solar_noon_day),
+            (TimestampType::DinnerTime(dinner_time), dinner_day),
+            (TimestampType::ScreensOff(screens_off), screens_off_day),
+            (bed_time, bed_day),
+        ];
+        let golden_hour = match (sunset, self.golden_hour_offset) {
+            (Some(sunset), _) => {
+                sunset_anchored.insert(EventId::GoldenHour);
+                Some(self.shift_on(0, sunset, -self.golden_hour_before_sunset))
+            }
+            (None, Some(offset)) => Some(self.shift_on(0, sunrise, offset)),
+            (None, None) => None,
+        };
+        if let Some((time, day)) = golden_hour {
+            events.push((TimestampType::GoldenHour(time), day));
+        }
+        for (label, anchor, offset) in &self.events {
+            let (anchor_time, offset) = match (anchor, sunset) {
+                (Anchor::Sunrise, _) => (sunrise, *offset),
+                (Anchor::Sunset, Some(sunset)) => {
+                    sunset_anchored.insert(EventId::Custom(label.clone()));
+                    (sunset, *offset)
+                }
+                // Without a known sunset there is nothing to anchor the event to.
+                (Anchor::Sunset, None) => continue,
+                // Shifted from the anchor of bed time in one go, like screens off.
+                (Anchor::Bed, _) => {
+                    bed_anchored.insert(EventId::Custom(label.clone()));
+                    (bed_anchor, bed_offset + *offset)
+                }
+            };
+            let (time, day) = self.shift_on(0, anchor_time, offset);
+            events.push((TimestampType::Custom(label.clone(), time), day));
+        }
+        // Disabled events are still computed, screens off and the wind down follow bed time.
+        events.retain(|(event, _)| {
+            self.enabled
+                .get(&event.get_name().to_lowercase())
+                .copied()
+                .unwrap_or(true)
+        });
+        // Events past midnight belong to the end of this day, not its start.
+        events.sort_by_key(|(event, day)| (*day, event.get_naive_time()));
+        let mut day_offsets = BTreeMap::new();
+        let mut alert_leads = BTreeMap::new();
+        let mut labels = BTreeMap::new();
+        for (event, day) in &events {
+            log::debug!(
+                "{:?} at {} (day {:+})",
+                event,
+                event.get_naive_time().format("%H:%M:%S"),
+                day
+            );
+            if *day != 0 {
+                day_offsets.insert(event.get_id(), *day);
+            }
+            if let Some(lead) = self.alert_leads.get(&event.get_name().to_lowercase()) {
+                alert_leads.insert(event.get_id(), *lead);
+            }
+            if let Some(text) = self.labels.get(&event.get_name().to_lowercase()) {
+                labels.insert(event.get_id(), text.clone());
+            }
+        }
+        Timestamps {
+            sunrise,
+            measured_sunrise,
+            sunset,
+            date: self.date,
+            events: events.into_iter().map(|(event, _)| event).collect(),
+            sunset_anchored,
+            bed_anchored,
+            day_offsets,
+            alert_leads,
+            labels,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    BeforeWake,
+    Day,
+    Evening,
+    Night,
+}
+
+impl Phase {
+    pub fn get_glyph(&self) -> &'static str {
+        match self {
+            Phase::BeforeWake => "🌅",
+            Phase::Day => "☀️",
+            Phase::Evening => "🌇",
+            Phase::Night => "🌙",
+        }
+    }
+}
+
+pub enum Derivation {
+    /// Shifted from `anchor` ("sunrise", "sunset" or "bed"), which happens at `at`.
+    Offset {
+        anchor: &'static str,
+        at: NaiveTime,
+        offset: chrono::Duration,
+    },
+    Midpoint {
+        sunrise: NaiveTime,
+        sunset: NaiveTime,
+    },
+}
+
+#[derive(Clone)]
+pub struct Timestamps {
+    pub sunrise: NaiveTime,
+    measured_sunrise: NaiveTime,
+    pub sunset: Option<NaiveTime>,
+    pub date: Option<NaiveDate>,
+    pub events: Vec<TimestampType>,
+    sunset_anchored: BTreeSet<EventId>,
+    bed_anchored: BTreeSet<EventId>,
+    day_offsets: BTreeMap<EventId, i64>,
+    alert_leads: BTreeMap<EventId, chrono::Duration>,
+    labels: BTreeMap<EventId, String>,
+}
+
+impl Timestamps {
+    pub fn from_sunrise(sunrise: NaiveTime, config: &Config) -> Timestamps {
+        Timestamps::from_sun_times(sunrise, None, config)
+    }
+    pub fn from_sun_times(
+        sunrise: NaiveTime,
+        sunset: Option<NaiveTime>,
+        config: &Config,
+    ) -> Timestamps {
+        TimestampsBuilder::from_config(config)
+            .sunrise(sunrise)
+            .sunset(sunset)
+            .build()
+    }
+    pub fn retain_events(&mut self, names: &[String]) {
+        self.events.retain(|event| {
+            names
+                .iter()
+                .any(|name| name.eq_ignore_ascii_case(event.get_name()))
+        });
+    }
+    /// The event called `name` (wake, lunch, ... or a label), unless `retain_events` dropped it.
+    pub fn get_event(&self, name: &str) -> Option<&TimestampType> {
+        self.events
+            .iter()
+            .find(|event| name.eq_ignore_ascii_case(event.get_name()))
+    }
+    pub fn wake_up(&self) -> Option<NaiveTime> {
+        self.get_event("wake").map(TimestampType::get_naive_time)
+    }
+    pub fn dinner(&self) -> Option<NaiveTime> {
+        self.get_event("dinner").map(TimestampType::get_naive_time)
+    }
+    pub fn bed(&self) -> Option<NaiveTime> {
+        self.get_event("bed").map(TimestampType::get_naive_time)
+    }
+    pub fn get_out_of_order_events(&self) -> Vec<(&TimestampType, &TimestampType)> {
+        let daily_order: Vec<&TimestampType> = ["wake", "lunch", "dinner", "screens", "bed"]
+            .iter()
+            .filter_map(|name| self.get_event(name))
+            .collect();
+        daily_order
+            .windows(2)
+            .filter(|pair| {
+                (self.get_day_offset(pair[0]), pair[0].get_naive_time())
+                    >= (self.get_day_offset(pair[1]), pair[1].get_naive_time())
+            })
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    }
+    pub fn get_day_offset(&self, event: &TimestampType) -> i64 {
+        self.day_offsets.get(&event.get_id()).copied().unwrap_or(0)
+    }
+    pub fn get_naive_date_time(&self, event: &TimestampType, date: NaiveDate) -> NaiveDateTime {
+        (date + chrono::Duration::days(self.get_day_offset(event))).and_time(event.get_naive_time())
+    }
+    /// Whether the schedule is pinned to a day that has not started yet.
+    pub fn is_pending(&self, now: NaiveDateTime) -> bool {
+        self.date.is_some_and(|date| date > now.date())
+    }
+    pub fn is_done(&self, event: &TimestampType, now: NaiveDateTime) -> bool {
+        let date = self.date.unwrap_or(now.date());
+        self.get_naive_date_time(event, date) <= now
+    }
+    /// The next event, `None` only for a schedule without events.
+    pub fn get_upcomming_timestamp(&self, clock: &dyn Clock) -> Option<&TimestampType> {
+        self.get_upcomming_timestamp_on(clock.now_date_time())
+    }
+    pub fn get_upcomming_timestamp_on(&self, now: NaiveDateTime) -> Option<&TimestampType> {
+        match self.date {
+            Some(date) if self.is_pending(now) => self
+                .events
+                .iter()
+                .min_by_key(|event| self.get_naive_date_time(event, date)),
+            _ => self.get_upcomming_timestamp_at(now.time()),
+        }
+    }
+    /// Which of the stretches split by wake up, dinner and bed time `now` falls into.
+    pub fn current_phase(&self, now: NaiveDateTime) -> Phase {
+        if self.is_pending(now) {
+            return Phase::BeforeWake;
+        }
+        let day = chrono::Duration::days(1);
+        let now = now.time().signed_duration_since(NaiveTime::MIN);
+        // Each marker's latest occurrence up to now, relative to today's midnight.
+        let latest = self
+            .events
+            .iter()
+            .filter_map(|event| {
+                let phase = match event {
+                    TimestampType::WakeUpTime(_) => Phase::Day,
+                    TimestampType::DinnerTime(_) => Phase::Evening,
+                    TimestampType::BedTime(_) => Phase::Night,
+                    _ => return None,
+                };
+                let mut at = event.get_naive_time().signed_duration_since(NaiveTime::MIN)
+                    + day * self.get_day_offset(event) as i32;
+                while at > now {
+                    at -= day;
+                }
+                Some((at, phase))
+            })
+            .max_by_key(|(at, _)| *at);
+        match latest {
+            // Midnight passed since bed time, so it is the night leading into today.
+            Some((at, Phase::Night)) if at < chrono::Duration::zero() => Phase::BeforeWake,
+            Some((_, phase)) => phase,
+            None => Phase::Day,
+        }
+    }
+    pub fn get_time_until(&self, event: &TimestampType, now: NaiveDateTime) -> chrono::Duration {
+        match self.date {
+            Some(date) if self.is_pending(now) => self
+                .get_naive_date_time(event, date)
+                .signed_duration_since(now),
+            _ => self.get_abs_time_diff(now.time(), event.get_naive_time()),
+        }
+    }
+    /// Events later in the day than `from`, earliest first.
+    pub fn iter_upcoming(&self, from: NaiveTime) -> impl Iterator<Item = &TimestampType> {
+        let mut upcoming: Vec<&TimestampType> = self
+            .events
+            .iter()
+            .filter(|event| event.get_naive_time() > from)
+            .collect();
+        upcoming.sort_by_key(|event| event.get_naive_time());
+        upcoming.into_iter()
+    }
+    pub fn get_upcomming_timestamp_at(&self, now: NaiveTime) -> Option<&TimestampType> {
+        self.iter_upcoming(now).next().or_else(|| {
+            self.events
+                .iter()
+                .min_by_key(|event| event.get_naive_time())
+        })
+    }
+    /// When the next alert fires and for which event, using the event's own lead if it has one.
+    /// An alert already passed today comes around again tomorrow.
+    pub fn next_alert(
+        &self,
+        now: NaiveTime,
+        lead: chrono::Duration,
+    ) -> Option<(NaiveTime, &TimestampType)> {
+        self.events
+            .iter()
+            .map(|event| {
+                let lead = self.get_alert_leads(event, &[lead])[0];
+                let alert_at = shift(event.get_naive_time(), -lead).0;
+                (self.get_abs_time_diff(now, alert_at), alert_at, event)
+            })
+            .min_by_key(|(until, _, _)| *until)
+            .map(|(_, alert_at, event)| (alert_at, event))
+    }
+    pub fn get_abs_time_diff(&self, first: NaiveTime, second: NaiveTime) -> chrono::Duration {
+        let diff = second.signed_duration_since(first);
+        if diff.num_seconds() < 0 {
+            diff + chrono::Duration::days(1)
+        } else {
+            diff
+        }
+    }
+    pub fn get_alert_lead(
+        &self,
+        event: &TimestampType,
+        lead: chrono::Duration,
+    ) -> chrono::Duration {
+        // A lead longer than the gap would fall while the previous event is still upcoming.
+        lead.min(self.get_gap_before(event) / 2)
+    }
+    /// The event's own lead if it has one, otherwise `default`, each capped like `get_alert_lead`.
+    pub fn get_alert_leads(
+        &self,
+        event: &TimestampType,
+        default: &[chrono::Duration],
+    ) -> Vec<chrono::Duration> {
+        match self.alert_leads.get(&event.get_id()) {
+            Some(lead) => vec![self.get_alert_lead(event, *lead)],
+            None => default
+                .iter()
+                .map(|lead| self.get_alert_lead(event, *lead))
+                .collect(),
+        }
+    }
+    /// The configured label of the event, its `Debug` label otherwise.
+    pub fn get_label(&self, event: &TimestampType) -> String {
+        match self.labels.get(&event.get_id()) {
+            Some(text) => text.clone(),
+            None => format!("{:?}", event),
+        }
+    }
+    /// Like `get_label`, falling back to the shorter label used in summaries.
+    pub fn get_summary_label(&self, event: &TimestampType) -> String {
+        match self.labels.get(&event.get_id()) {
+            Some(text) => text.clone(),
+            None => event.get_summary_label().to_string(),
+        }
+    }
+    /// Consecutive events less than `min_gap` apart, the countdown would flicker between them.
+    pub fn get_close_events(
+        &self,
+        min_gap: chrono::Duration,
+    ) -> Vec<(&TimestampType, &TimestampType)> {
+        if self.events.len() < 2 {
+            return Vec::new();
+        }
+        self.events
+            .iter()
+            .filter_map(|event| Some((self.get_previous_timestamp(event)?, event)))
+            .filter(|(previous, event)| {
+                self.get_abs_time_diff(previous.get_naive_time(), event.get_naive_time()) < min_gap
+            })
+            .collect()
+    }
+    /// The first event of the day is preceded by the last one of the prior day.
+    pub fn get_previous_timestamp(&self, event: &TimestampType) -> Option<&TimestampType> {
+        let index = self.events.iter().position(|e| e == event).unwrap_or(0);
+        let count = self.events.len();
+        self.events.get((index + count).checked_sub(1)? % count)
+    }
+    /// Moves `event` by up to `by`, keeping it a minute clear of its neighbours so the
+    /// order of the day holds. Returns how far it moved.
+    pub fn adjust_event(
+        &mut self,
+        event: &TimestampType,
+        by: chrono::Duration,
+    ) -> chrono::Duration {
+        let Some(index) = self.events.iter().position(|e| e == event) else {
+            return chrono::Duration::zero();
+        };
+        let minute = chrono::Duration::minutes(1);
+        let next = &self.events[(index + 1) % self.events.len()];
+        let earliest = (minute - self.get_gap_before(event)).min(chrono::Duration::zero());
+        let latest = (self.get_gap_before(next) - minute).max(chrono::Duration::zero());
+        let by = by.clamp(earliest, latest);
+        let (time, wrapped) = shift(event.get_naive_time(), by);
+        match self.get_day_offset(event) + wrapped {
+            0 => self.day_offsets.remove(&event.get_id()),
+            day => self.day_offsets.insert(event.get_id(), day),
+        };
+        self.events[index] = event.with_time(time);
+        by
+    }
+    pub fn get_gap_before(&self, event: &TimestampType) -> chrono::Duration {
+        let Some(previous) = self.get_previous_timestamp(event) else {
+            return chrono::Duration::days(1);
+        };
+        match self.get_abs_time_diff(previous.get_naive_time(), event.get_naive_time()) {
+            gap if gap.is_zero() => chrono::Duration::days(1),
+            gap => gap,
+        }
+    }
+    pub fn get_derivation(&self, event: &TimestampType) -> Derivation {
+        let since = |anchor: NaiveTime, anchor_day: i64| {
+            event.get_naive_time().signed_duration_since(anchor)
+                + chrono::Duration::days(self.get_day_offset(event) - anchor_day)
+        };
+        match (event, self.sunset) {
+            (TimestampType::SolarNoon(_), Some(sunset)) => Derivation::Midpoint {
+                sunrise: self.measured_sunrise,
+                sunset,
+            },
+            _ if self.is_bed_anchored(event) => match self.get_event("bed") {
+                Some(bed_time) => Derivation::Offset {
+                    anchor: "bed",
+                    at: bed_time.get_naive_time(),
+                    offset: since(bed_time.get_naive_time(), self.get_day_offset(bed_time)),
+                },
+                None => Derivation::Offset {
+                    anchor: "sunrise",
+                    at: self.sunrise,
+                    offset: since(self.sunrise, 0),
+                },
+            },
+            (_, Some(sunset)) if self.sunset_anchored.contains(&event.get_id()) => {
+                Derivation::Offset {
+                    anchor: "sunset",
+                    at: sunset,
+                    offset: since(sunset, 0),
+                }
+            }
+            _ => Derivation::Offset {
+                anchor: "sunrise",
+                at: self.sunrise,
+                offset: since(self.sunrise, 0),
+            },
+        }
+    }
+    fn is_bed_anchored(&self, event: &TimestampType) -> bool {
+        matches!(event, TimestampType::ScreensOff(_)) || self.bed_anchored.contains(&event.get_id())
+    }
+    pub fn get_offset_label(&self, event: &TimestampType) -> String {
+        let bed_time = self.get_event("bed");
+        let (anchor, anchor_day, prefix) = match (self.sunset, bed_time) {
+            (Some(sunset), _) if self.sunset_anchored.contains(&event.get_id()) => {
+                (sunset, 0, "sunset ")
+            }
+            (_, Some(bed_time)) if self.is_bed_anchored(event) => (
+                bed_time.get_naive_time(),
+                self.get_day_offset(bed_time),
+                "bed ",
+            ),
+            _ => (self.sunrise, 0, ""),
+        };
+        let offset = event.get_naive_time().signed_duration_since(anchor)
+            + chrono::Duration::days(self.get_day_offset(event) - anchor_day);
+        if offset.num_hours() == 0 {
+            format!("{}{:+}m", prefix, offset.num_minutes())
+        } else {
+            format!("{}{:+}h", prefix, offset.num_seconds() as f32 / 3600.0)
+        }
+    }
+}
+
+impl Timestamps {
+    /// One line per event, its time written with `format_time` and its padded label with `style`.
+    pub fn write_summary(
+        &self,
+        out: &mut dyn std::fmt::Write,
+        format_time: &dyn Fn(NaiveTime) -> String,
+        style: &dyn Fn(&str) -> String,
+    ) -> std::fmt::Result {
+        for (index, event) in self.events.iter().enumerate() {
+            if index > 0 {
+                writeln!(out)?;
+            }
+            write!(
+                out,
+                " {}{} ({})",
+                style(&format!(
+                    "{:<16}",
+                    format!("{}:", self.get_summary_label(event))
+                )),
+                format_time(event.get_naive_time()),
+                self.get_offset_label(event)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The summary printed at startup, in 24 hour time and without styling.
+impl std::fmt::Display for Timestamps {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.write_summary(f, &|time| time.format("%H:%M").to_string(), &|label| {
+            label.to_string()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_config_keeps_the_defaults_for_missing_keys() {
+        let config = Config::parse("dinner_time_since_sunrise_in_hours = 5.0\n").unwrap();
+        assert_eq!(config.dinner_time_since_sunrise_in_hours, 5.0);
+        assert_eq!(
+            config.sunrise_modifier_for_wake_up_time_in_minutes,
+            SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES
+        );
+        assert_eq!(
+            config.sunrise_modifier_for_bed_time_in_hours,
+            SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS
+        );
+    }
+
+    #[test]
+    fn empty_config_is_the_default_one() {
+        let config = Config::parse("").unwrap();
+        let default = Config::default();
+        assert_eq!(
+            config.sunrise_modifier_for_wake_up_time_in_minutes,
+            default.sunrise_modifier_for_wake_up_time_in_minutes
+        );
+        assert_eq!(
+            config.dinner_time_since_sunrise_in_hours,
+            default.dinner_time_since_sunrise_in_hours
+        );
+        assert_eq!(
+            config.sunrise_modifier_for_bed_time_in_hours,
+            default.sunrise_modifier_for_bed_time_in_hours
+        );
+    }
+
+    fn time(text: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(text, "%H:%M").unwrap()
+    }
+
+    // A Wednesday, so no weekend delay applies.
+    fn fixture_date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2026, 6, 10).unwrap()
+    }
+
+    fn builder(sunrise: &str) -> TimestampsBuilder {
+        TimestampsBuilder::new()
+            .sunrise(time(sunrise))
+            .date(Some(fixture_date()))
+            .zone(Zone(Some(chrono_tz::UTC)))
+    }
+
+    // On the pinned day itself, so the outcome does not depend on today's date.
+    fn upcoming_name(timestamps: &Timestamps, now: NaiveTime) -> &str {
+        timestamps
+            .get_upcomming_timestamp_on(fixture_date().and_time(now))
+            .unwrap()
+            .get_name()
+    }
+
+    #[test]
+    fn after_bed_time_the_upcoming_event_is_tomorrows_wake_up() {
+        let timestamps = builder("07:00").build();
+        assert_eq!(upcoming_name(&timestamps, time("23:00")), "wake");
+        assert_eq!(upcoming_name(&timestamps, time("23:59")), "wake");
+        assert_eq!(upcoming_name(&timestamps, time("00:00")), "wake");
+    }
+
+    #[test]
+    fn upcoming_event_across_the_day() {
+        let timestamps = builder("07:00").build();
+        for (now, expected) in [
+            ("05:00", "wake"),
+            ("07:00", "lunch"),
+            ("12:45", "noon"),
+            ("14:00", "dinner"),
+            ("19:00", "screens"),
+            ("22:00", "bed"),
+        ] {
+            assert_eq!(
+                upcoming_name(&timestamps, time(now)),
+                expected,
+                "at {}",
+                now
+            );
+        }
+    }
+
+    #[test]
+    fn each_event_stays_upcoming_until_its_second_passes() {
+        let timestamps = builder("07:00").build();
+        let second = chrono::Duration::seconds(1);
+        let names: Vec<&str> = timestamps.events.iter().map(|e| e.get_name()).collect();
+        for (index, event) in timestamps.events.iter().enumerate() {
+            let next = names[(index + 1) % names.len()];
+            let at = event.get_naive_time();
+            let before = timestamps.get_upcomming_timestamp_at(at - second).unwrap();
+            assert_eq!(before.get_name(), event.get_name());
+            let at_event = timestamps.get_upcomming_timestamp_at(at).unwrap();
+            assert_eq!(at_event.get_name(), next);
+            let after = timestamps.get_upcomming_timestamp_at(at + second).unwrap();
+            assert_eq!(after.get_name(), next);
+        }
+    }
+
+    #[test]
+    fn bed_time_follows_a_later_sunset() {
+        let bed_at = |sunset| {
+            builder("05:00")
+                .sunset(Some(time(sunset)))
+                .bed_offset_from_sunset(chrono::Duration::hours(1))
+                .build()
+                .bed()
+                .unwrap()
+        };
+        assert_eq!(bed_at("20:00"), time("21:00"));
+        assert_eq!(bed_at("21:30"), time("22:30"));
+        assert!(bed_at("21:30") > bed_at("20:00"));
+        // The sunrise stays the same, only the sunset moved bed time.
+        let timestamps = builder("05:00")
+            .sunset(Some(time("21:30")))
+            .bed_offset_from_sunset(chrono::Duration::hours(1))
+            .build();
+        let bed = timestamps.get_event("bed").unwrap();
+        assert_eq!(timestamps.get_offset_label(bed), "sunset +1h");
+    }
+
+    #[test]
+    fn late_sunrise_puts_bed_time_after_midnight_at_the_end_of_the_day() {
+        let timestamps = builder("08:00")
+            .bed_offset(chrono::Duration::hours(17))
+            .build();
+        let bed = timestamps.get_event("bed").unwrap();
+        assert_eq!(bed.get_naive_time(), time("01:00"));
+        assert_eq!(timestamps.get_day_offset(bed), 1);
+        assert_eq!(timestamps.events.last(), Some(bed));
+        assert!(timestamps.get_out_of_order_events().is_empty());
+        let upcoming = timestamps
+            .get_upcomming_timestamp_at(time("23:45"))
+            .unwrap();
+        assert_eq!(upcoming.get_name(), "screens");
+        let upcoming = timestamps
+            .get_upcomming_timestamp_at(time("00:30"))
+            .unwrap();
+        assert_eq!(upcoming.get_name(), "bed");
+        assert_eq!(timestamps.get_offset_label(bed), "+17h");
+    }
+
+    #[test]
+    fn upcoming_events_are_walked_in_order() {
+        let timestamps = builder("07:00")
+            .event("Tea", Anchor::Sunrise, chrono::Duration::hours(9))
+            .build();
+        let names: Vec<&str> = timestamps
+            .iter_upcoming(time("12:45"))
+            .map(TimestampType::get_name)
+            .collect();
+        assert_eq!(names, ["noon", "Tea", "dinner", "screens", "bed"]);
+        let all: Vec<NaiveTime> = timestamps
+            .iter_upcoming(NaiveTime::MIN)
+            .map(TimestampType::get_naive_time)
+            .collect();
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(all.len(), timestamps.events.len());
+    }
+
+    #[test]
+    fn nothing_is_upcoming_after_the_last_event_or_without_events() {
+        let timestamps = builder("07:00").build();
+        assert_eq!(timestamps.iter_upcoming(time("22:30")).count(), 0);
+        assert_eq!(timestamps.iter_upcoming(time("23:59")).count(), 0);
+        let empty = builder("07:00")
+            .enabled("wake", false)
+            .enabled("lunch", false)
+            .enabled("noon", false)
+            .enabled("dinner", false)
+            .enabled("screens", false)
+            .enabled("bed", false)
+            .build();
+        assert_eq!(empty.iter_upcoming(NaiveTime::MIN).count(), 0);
+    }
+
+    // Lunch five hours after a 1:00 sunrise spans the night the Berlin clocks change.
+    fn lunch_on(date: (i32, u32, u32)) -> NaiveTime {
+        TimestampsBuilder::new()
+            .sunrise(time("01:00"))
+            .date(NaiveDate::from_ymd_opt(date.0, date.1, date.2))
+            .zone(Zone(Some(chrono_tz::Europe::Berlin)))
+            .lunch_offset(chrono::Duration::hours(5))
+            .build()
+            .events
+            .iter()
+            .find(|event| event.get_name() == "lunch")
+            .unwrap()
+            .get_naive_time()
+    }
+
+    #[test]
+    fn offsets_span_real_hours_across_daylight_saving_time() {
+        assert_eq!(lunch_on((2026, 3, 28)), time("06:00"));
+        // The clocks skip an hour, five real hours end at 7:00.
+        assert_eq!(lunch_on((2026, 3, 29)), time("07:00"));
+        // The clocks repeat an hour, five real hours end at 5:00.
+        assert_eq!(lunch_on((2026, 10, 25)), time("05:00"));
+        assert_eq!(lunch_on((2026, 10, 26)), time("06:00"));
+    }
+
+    #[test]
+    fn events_are_labelled_for_humans() {
+        let at = time("07:00");
+        assert_eq!(
+            format!("{:?}", TimestampType::WakeUpTime(at)),
+            "Wake up time"
+        );
+        assert_eq!(
+            format!("{:?}", TimestampType::DinnerTime(at)),
+            "Optimal evening dinner time"
+        );
+        assert_eq!(format!("{:?}", TimestampType::BedTime(at)), "Bedtime");
+    }
+
+    #[test]
+    fn next_alert_is_the_closest_lead_ahead() {
+        let timestamps = builder("07:00").build();
+        let minutes = chrono::Duration::minutes;
+        let next = |now, lead| {
+            let (at, event) = timestamps.next_alert(time(now), minutes(lead)).unwrap();
+            (at.format("%H:%M").to_string(), event.get_name())
+        };
+        assert_eq!(next("06:00", 10), (String::from("06:35"), "wake"));
+        assert_eq!(next("06:35", 10), (String::from("06:35"), "wake"));
+        assert_eq!(next("06:36", 10), (String::from("12:20"), "lunch"));
+        assert_eq!(next("06:36", 0), (String::from("06:45"), "wake"));
+        // Half the 30 minutes between lunch and solar noon is as early as the noon alert gets.
+        assert_eq!(next("12:31", 20), (String::from("12:45"), "noon"));
+        // Past the last alert of the day comes tomorrow's first.
+        assert_eq!(next("23:00", 10), (String::from("06:35"), "wake"));
+    }
+
+    #[test]
+    fn next_alert_uses_the_lead_of_the_event() {
+        let timestamps = builder("07:00")
+            .alert_lead("lunch", chrono::Duration::minutes(60))
+            .build();
+        let (at, event) = timestamps
+            .next_alert(time("07:00"), chrono::Duration::minutes(10))
+            .unwrap();
+        assert_eq!((at, event.get_name()), (time("11:30"), "lunch"));
+    }
+
+    #[test]
+    fn custom_event_named_like_a_built_in_keeps_its_own_settings() {
+        let timestamps = builder("06:00")
+            .sunset(Some(time("21:00")))
+            .event("Bedtime", Anchor::Sunset, chrono::Duration::hours(1))
+            .label("bed", "Sleep")
+            .build();
+        let bed = timestamps.get_event("bed").unwrap();
+        let custom = timestamps.get_event("Bedtime").unwrap();
+        assert_eq!(timestamps.get_label(bed), "Sleep");
+        assert_eq!(timestamps.get_label(custom), "Bedtime");
+        assert_eq!(timestamps.get_offset_label(bed), "+15.5h");
+        assert_eq!(timestamps.get_offset_label(custom), "sunset +1h");
+    }
+
+    #[test]
+    fn schedule_without_events_has_no_upcoming_event() {
+        let timestamps = [
+            "wake", "lunch", "noon", "dinner", "screens", "bed", "golden",
+        ]
+        .iter()
+        .fold(builder("06:00"), |builder, name| {
+            builder.enabled(name, false)
+        })
+        .build();
+        assert!(timestamps.events.is_empty());
+        assert!(timestamps
+            .get_upcomming_timestamp_at(time("12:00"))
+            .is_none());
+        let now = NaiveDate::from_ymd_opt(2026, 6, 10)
+            .unwrap()
+            .and_time(time("12:00"));
+        assert!(timestamps.get_upcomming_timestamp_on(now).is_none());
+        let bed = TimestampType::BedTime(time("22:00"));
+        assert!(timestamps.get_previous_timestamp(&bed).is_none());
+        assert_eq!(timestamps.get_gap_before(&bed), chrono::Duration::days(1));
+        assert!(timestamps
+            .next_alert(time("12:00"), chrono::Duration::zero())
+            .is_none());
+    }
+
+    #[test]
+    fn bed_after_midnight_is_done_once_that_moment_passed() {
+        let timestamps = builder("08:40").build();
+        let bed = timestamps.get_event("bed").unwrap();
+        assert_eq!(bed.get_naive_time(), time("00:10"));
+        assert_eq!(timestamps.get_day_offset(bed), 1);
+        let at = |day, text| {
+            NaiveDate::from_ymd_opt(2026, 6, day)
+                .unwrap()
+                .and_time(time(text))
+        };
+        assert!(!timestamps.is_done(bed, at(10, "23:59")));
+        assert!(timestamps.is_done(bed, at(11, "00:10")));
+        assert!(timestamps.is_done(bed, at(11, "00:20")));
+    }
+
+    #[test]
+    fn config_times_accept_the_sunrise_formats_only() {
+        assert_eq!(parse_config_time("6:30"), Some(time("06:30")));
+        assert_eq!(parse_config_time("6:30 PM"), Some(time("18:30")));
+        assert_eq!(parse_config_time("nope"), None);
+        assert_eq!(parse_config_time("6:75"), None);
+    }
+
+    #[test]
+    fn summary_shares_its_layout_with_the_styled_one() {
+        let timestamps = builder("07:00").build();
+        let plain = timestamps.to_string();
+        assert!(plain.starts_with(" Wake up time:   06:45 (-15m)\n Lunch:          12:30 (+5.5h)"));
+        let mut styled = String::new();
+        timestamps
+            .write_summary(
+                &mut styled,
+                &|time| time.format("%I:%M %p").to_string(),
+                &|label| format!("*{}*", label.trim_end()),
+            )
+            .unwrap();
+        assert!(styled.starts_with(" *Wake up time:*06:45 AM (-15m)"));
+        assert_eq!(styled.lines().count(), plain.lines().count());
+    }
+
+    #[test]
+    fn solar_noon_is_halfway_across_the_measured_day() {
+        let timestamps = builder("06:30")
+            .measured_sunrise(time("04:43"))
+            .sunset(Some(time("21:21")))
+            .build();
+        let noon = timestamps.get_event("noon").unwrap();
+        assert_eq!(noon.get_naive_time(), time("13:02"));
+        assert!(matches!(
+            timestamps.get_derivation(noon),
+            Derivation::Midpoint { sunrise, .. } if sunrise == time("04:43")
+        ));
+        // Wake up still follows the clamped sunrise.
+        assert_eq!(timestamps.wake_up(), Some(time("06:15")));
+    }
+
+    #[test]
+    fn events_derived_from_bed_time_are_labelled_relative_to_it() {
+        let timestamps = builder("06:42")
+            .event(
+                "Dim the lights",
+                Anchor::Bed,
+                chrono::Duration::minutes(-90),
+            )
+            .build();
+        let screens = timestamps.get_event("screens").unwrap();
+        assert_eq!(timestamps.get_offset_label(screens), "bed -1h");
+        let dim = timestamps.get_event("Dim the lights").unwrap();
+        assert_eq!(timestamps.get_offset_label(dim), "bed -1.5h");
+        let bed = timestamps.get_event("bed").unwrap();
+        assert_eq!(timestamps.get_offset_label(bed), "+15.5h");
+    }
+}
@@ -1,177 +1,2317 @@
+#[cfg(feature = "mqtt")]
+mod mqtt;
+#[cfg(feature = "serve")]
+mod serve;
+
 use soloud::*;
-use std::cmp::Ordering;
-use std::env;
-use std::io::Write;
+use std::collections::BTreeMap;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::Mutex;
+
+use clap::{CommandFactory, FromArgMatches, Parser};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal;
+use owo_colors::{OwoColorize, Stream::Stdout};
+use unicode_width::UnicodeWidthChar;
+
+use chrono::{format, DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+use prompty::{
+    compute_sunrise, compute_sunset, Clock, Config, ConfigError, Derivation, EventId, SystemClock,
+    TimestampType, Timestamps, TimestampsBuilder, Zone, DEFAULT_PROFILE,
+    DINNER_TIME_SINCE_SUNRISE_IN_HOURS, GOLDEN_HOUR_BEFORE_SUNSET_IN_MINUTES,
+    LUNCH_TIME_SINCE_SUNRISE_IN_HOURS, MAX_WAKEUP_TIME, MIN_WAKEUP_TIME,
+    SCREENS_OFF_BEFORE_BED_IN_MINUTES, SOLAR_NOON_SINCE_SUNRISE_IN_HOURS, SUNRISE_FORMATS,
+    SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS, SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES,
+};
+use serde::{Deserialize, Serialize};
+
+const SUNRISE_API_URL: &str = "https://api.sunrise-sunset.org/json";
+const TWELVE_HOUR_FORMAT: &str = "%I:%M %p";
+const SECONDS_FORMAT: &str = "%H:%M:%S";
+const TWELVE_HOUR_SECONDS_FORMAT: &str = "%I:%M:%S %p";
+
+static TIME_FORMAT: std::sync::OnceLock<&str> = std::sync::OnceLock::new();
+
+fn format_time(time: NaiveTime) -> String {
+    time.format(TIME_FORMAT.get().unwrap_or(&"%H:%M"))
+        .to_string()
+}
 
-use chrono::{format, Local, NaiveTime};
+// The wall clock always shows seconds, in 12 hour time along with the other times.
+fn format_clock(time: NaiveTime) -> String {
+    match TIME_FORMAT.get() {
+        Some(&TWELVE_HOUR_FORMAT) | Some(&TWELVE_HOUR_SECONDS_FORMAT) => {
+            time.format(TWELVE_HOUR_SECONDS_FORMAT).to_string()
+        }
+        _ => time.format(SECONDS_FORMAT).to_string(),
+    }
+}
 
-const MIN_WAKEUP_TIME: &str = "6:30";
-const MAX_WAKEUP_TIME: &str = "8:22";
-const SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES: i64 = 15;
-const DINNER_TIME_SINCE_SUNRISE_IN_HOURS: f32 = 11.5;
-const SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS: f32 = 15.5;
+#[derive(Serialize, Default)]
+struct Schedule {
+    sunrise: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sunset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wake_up: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    lunch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    solar_noon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    golden_hour: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dinner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    screens_off: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bed: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    custom: BTreeMap<String, String>,
+}
 
-#[derive(PartialEq)]
-enum TimestampType {
-    WakeUpTime(NaiveTime),
-    BedTime(NaiveTime),
-    DinnerTime(NaiveTime),
+impl From<&Timestamps> for Schedule {
+    fn from(timestamps: &Timestamps) -> Schedule {
+        let mut schedule = Schedule {
+            sunrise: timestamps.sunrise.format("%H:%M").to_string(),
+            sunset: timestamps
+                .sunset
+                .map(|sunset| sunset.format("%H:%M").to_string()),
+            ..Default::default()
+        };
+        for event in &timestamps.events {
+            let time = event.get_naive_time().format("%H:%M").to_string();
+            match event {
+                TimestampType::WakeUpTime(_) => schedule.wake_up = Some(time),
+                TimestampType::LunchTime(_) => schedule.lunch = Some(time),
+                TimestampType::SolarNoon(_) => schedule.solar_noon = Some(time),
+                TimestampType::DinnerTime(_) => schedule.dinner = Some(time),
+                TimestampType::ScreensOff(_) => schedule.screens_off = Some(time),
+                TimestampType::BedTime(_) => schedule.bed = Some(time),
+                TimestampType::GoldenHour(_) => schedule.golden_hour = Some(time),
+                TimestampType::Custom(label, _) => {
+                    schedule.custom.insert(label.clone(), time);
+                }
+            }
+        }
+        schedule
+    }
 }
 
-impl std::fmt::Debug for TimestampType {
+// Custom labels become valid shell names, "Evening walk" is exported as EVENING_WALK.
+fn env_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn to_env(timestamps: &Timestamps) -> String {
+    let mut lines = vec![format!("SUNRISE={}", timestamps.sunrise.format("%H:%M"))];
+    if let Some(sunset) = timestamps.sunset {
+        lines.push(format!("SUNSET={}", sunset.format("%H:%M")));
+    }
+    for event in &timestamps.events {
+        lines.push(format!(
+            "{}={}",
+            env_name(event.get_name()),
+            event.get_naive_time().format("%H:%M")
+        ));
+    }
+    lines.join("\n")
+}
+
+fn to_ical(timestamps: &Timestamps, date: NaiveDate) -> String {
+    let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%SZ");
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        String::from("PRODID:-//prompty//prompty//EN"),
+    ];
+    for (index, event) in timestamps.events.iter().enumerate() {
+        // Floating times, so the calendar app shows them in its own local time.
+        let start = timestamps.get_naive_date_time(event, date);
+        lines.push(String::from("BEGIN:VEVENT"));
+        lines.push(format!("UID:{}-{}@prompty", start.format("%Y%m%d"), index));
+        lines.push(format!("DTSTAMP:{}", stamp));
+        lines.push(format!("DTSTART:{}", start.format("%Y%m%dT%H%M%S")));
+        lines.push(format!(
+            "SUMMARY:{}",
+            timestamps
+                .get_label(event)
+                .replace('\\', "\\\\")
+                .replace(',', "\\,")
+                .replace(';', "\\;")
+        ));
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+    lines.iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+enum FetchError {
+    Network(String),
+    MalformedResponse(String),
+}
+
+impl std::fmt::Debug for FetchError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TimestampType::WakeUpTime(_) => write!(f, "Wake up time"),
-            TimestampType::DinnerTime(_) => {
-                write!(f, "Optimal evening dinner time")
+            FetchError::Network(reason) => {
+                write!(f, "Could not reach the sunrise service: {}", reason)
+            }
+            FetchError::MalformedResponse(reason) => {
+                write!(
+                    f,
+                    "The sunrise service sent an unexpected response: {}",
+                    reason
+                )
             }
-            TimestampType::BedTime(_) => write!(f, "Bedtime"),
         }
     }
 }
 
-impl TimestampType {
-    fn get_naive_time(&self) -> NaiveTime {
-        match self {
-            TimestampType::WakeUpTime(v)
-            | TimestampType::BedTime(v)
-            | TimestampType::DinnerTime(v) => *v,
+#[derive(Deserialize)]
+struct SunriseResponse {
+    status: String,
+    results: Option<SunriseResults>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+struct SunriseResults {
+    sunrise: String,
+    sunset: String,
+}
+
+#[derive(Deserialize, Serialize)]
+struct CachedSunTimes {
+    date: String,
+    lat: f64,
+    lon: f64,
+    #[serde(flatten)]
+    results: SunriseResults,
+}
+
+fn sun_times_cache_path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("prompty").join("sun_times.json"))
+}
+
+fn read_cached_sun_times(lat: f64, lon: f64, date: NaiveDate) -> Option<SunriseResults> {
+    let content = std::fs::read_to_string(sun_times_cache_path()?).ok()?;
+    let cached: CachedSunTimes = serde_json::from_str(&content).ok()?;
+    (cached.date == date.format("%Y-%m-%d").to_string() && cached.lat == lat && cached.lon == lon)
+        .then_some(cached.results)
+}
+
+fn write_cached_sun_times(lat: f64, lon: f64, date: NaiveDate, results: SunriseResults) {
+    let Some(path) = sun_times_cache_path() else {
+        return;
+    };
+    let cached = CachedSunTimes {
+        date: date.format("%Y-%m-%d").to_string(),
+        lat,
+        lon,
+        results,
+    };
+    // A cache that cannot be written only costs another request next time.
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, serde_json::to_string(&cached).unwrap_or_default()));
+    if let Err(e) = written {
+        log::warn!("Could not cache the sun times in {}: {}", path.display(), e);
+    }
+}
+
+fn parse_utc_time(value: &str, zone: Zone) -> Result<NaiveTime, FetchError> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| zone.from_utc(time.with_timezone(&Utc)).time())
+        .map_err(|e| FetchError::MalformedResponse(e.to_string()))
+}
+
+fn request_sun_times(lat: f64, lon: f64, date: NaiveDate) -> Result<SunriseResults, FetchError> {
+    let body = ureq::get(SUNRISE_API_URL)
+        .query("lat", &lat.to_string())
+        .query("lng", &lon.to_string())
+        .query("date", &date.format("%Y-%m-%d").to_string())
+        .query("formatted", "0")
+        .timeout(std::time::Duration::from_secs(10))
+        .call()
+        .map_err(|e| FetchError::Network(e.to_string()))?
+        .into_string()
+        .map_err(|e| FetchError::Network(e.to_string()))?;
+    let response: SunriseResponse =
+        serde_json::from_str(&body).map_err(|e| FetchError::MalformedResponse(e.to_string()))?;
+    let results = match (response.status.as_str(), response.results) {
+        ("OK", Some(results)) => results,
+        (status, _) => return Err(FetchError::MalformedResponse(format!("status {}", status))),
+    };
+    Ok(results)
+}
+
+const FETCH_ATTEMPTS: u32 = 3;
+const FETCH_RETRY_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+// Only network failures are retried, a malformed response will not improve by asking again.
+fn request_sun_times_retrying(
+    lat: f64,
+    lon: f64,
+    date: NaiveDate,
+    attempts: u32,
+) -> Result<SunriseResults, FetchError> {
+    let mut delay = FETCH_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match request_sun_times(lat, lon, date) {
+            Err(FetchError::Network(reason)) if attempt < attempts => {
+                log::warn!(
+                    "Attempt {} of {} to reach the sunrise service failed: {}",
+                    attempt,
+                    attempts,
+                    reason
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(FetchError::Network(reason)) if attempts > 1 => {
+                return Err(FetchError::Network(format!(
+                    "{} (gave up after {} attempts)",
+                    reason, attempts
+                )))
+            }
+            result => return result,
+        }
+    }
+}
+
+fn fetch_sun_times(
+    lat: f64,
+    lon: f64,
+    date: NaiveDate,
+    zone: Zone,
+    refresh: bool,
+    attempts: u32,
+) -> Result<(NaiveTime, NaiveTime), FetchError> {
+    let results = match read_cached_sun_times(lat, lon, date).filter(|_| !refresh) {
+        Some(results) => {
+            log::debug!("Using the cached sun times for {}", date);
+            results
         }
+        None => {
+            let results = request_sun_times_retrying(lat, lon, date, attempts)?;
+            write_cached_sun_times(lat, lon, date, results.clone());
+            results
+        }
+    };
+    Ok((
+        parse_utc_time(&results.sunrise, zone)?,
+        parse_utc_time(&results.sunset, zone)?,
+    ))
+}
+
+const USAGE: &str = "Usage: prompty H:M | prompty --coords LAT,LON | prompty --lat LAT --lon LON";
+
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Counts down to the daily events derived from today's sunrise."
+)]
+struct Cli {
+    /// Sunrise time as %H:%M (9:47), %H:%M:%S (9:47:12) or %I:%M %p (9:47 AM), optionally after a YYYY-MM-DD date, read from PROMPTY_SUNRISE or stdin when missing
+    #[arg(conflicts_with_all = ["coords", "lat", "lon"])]
+    sunrise: Option<String>,
+    /// Compute the sunrise offline for the given coordinates
+    #[arg(long, value_name = "LAT,LON", value_parser = parse_coords, allow_hyphen_values = true, conflicts_with_all = ["lat", "lon"])]
+    coords: Option<(f64, f64)>,
+    /// Minutes before an event at which the alert fires, repeat for earlier warnings
+    #[arg(long, value_name = "MINUTES")]
+    lead: Vec<i64>,
+    /// Play this sound file instead of the built-in alert
+    #[arg(long, value_name = "PATH")]
+    sound: Option<PathBuf>,
+    /// Alert volume between 0.0 and 1.0
+    #[arg(long)]
+    volume: Option<f32>,
+    /// Loop the alert sound until Enter is pressed
+    #[arg(long)]
+    persistent_alert: bool,
+    /// Announce the upcoming event with espeak/say instead of the alert sound
+    #[arg(long)]
+    tts: bool,
+    /// Read the schedule out with espeak/say before the countdown starts
+    #[arg(long, conflicts_with = "no_audio")]
+    speak_summary: bool,
+    /// Explain how every event time was derived after the summary
+    #[arg(short, long)]
+    verbose: bool,
+    /// Show a live countdown for every event instead of just the next one
+    #[arg(long)]
+    all: bool,
+    /// Print today's schedule as JSON and exit
+    #[arg(long)]
+    json: bool,
+    /// Print the configuration in effect, the config file with the flags applied, as TOML and exit
+    #[arg(long)]
+    dump_config: bool,
+    /// Print today's schedule as shell variables (WAKE=06:15) and exit
+    #[arg(long, conflicts_with = "json")]
+    env: bool,
+    /// Write today's schedule as an iCalendar file and exit
+    #[arg(long, value_name = "PATH")]
+    ical: Option<PathBuf>,
+    /// Print the next event and its countdown once and exit
+    #[arg(long)]
+    oneshot: bool,
+    /// Like --oneshot, as if it was this time of day
+    #[arg(long, value_name = "H:M", value_parser = parse_time_arg)]
+    at: Option<NaiveTime>,
+    /// Skip the per-second countdown output but keep alerting
+    #[arg(long)]
+    quiet: bool,
+    /// Go straight to the countdown without printing the schedule first
+    #[arg(long)]
+    no_summary: bool,
+    /// Start the countdown line with the current time, [14:22:05]
+    #[arg(long)]
+    clock: bool,
+    /// Stream a JSON object per tick and per alert to stdout instead of the countdown line
+    #[arg(long, conflicts_with_all = ["all", "bar", "clock", "quiet", "persistent_alert", "bell"])]
+    jsonl: bool,
+    /// Only count down to and alert for these events (wake, lunch, noon, dinner, screens, bed, golden or a custom label)
+    #[arg(long, value_name = "EVENT")]
+    only: Vec<String>,
+    /// Count down to this one event only and exit once it has passed
+    #[arg(long, value_name = "EVENT", conflicts_with = "only")]
+    until: Option<String>,
+    /// Use the offsets of this [profiles.NAME] table from the config
+    #[arg(long, default_value = DEFAULT_PROFILE)]
+    profile: String,
+    /// Fail instead of warning when the events are out of order
+    #[arg(long)]
+    strict: bool,
+    /// Draw a progress bar from the previous event to the upcomming one
+    #[arg(long, conflicts_with = "all")]
+    bar: bool,
+    /// Also show how long ago the previous event was
+    #[arg(long, conflicts_with = "all")]
+    since: bool,
+    /// Print a glyph for the current part of the day and exit
+    #[arg(long)]
+    phase: bool,
+    /// Exit after the first alert instead of counting down to the next event
+    #[arg(long)]
+    exit_after_alert: bool,
+    /// Seconds after an alert during which 's' snoozes it
+    #[arg(long, value_name = "SECONDS")]
+    snooze_window: Option<u64>,
+    /// Seconds between two countdown updates
+    #[arg(long, value_name = "SECONDS", default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    interval: u64,
+    /// Recompute the schedule every this many minutes and show it again if it changed
+    #[arg(long, value_name = "MINUTES", value_parser = clap::value_parser!(u64).range(1..))]
+    watch: Option<u64>,
+    /// Countdown line template using {label}, {at}, {h}, {m} and {s}
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_COUNTDOWN_FORMAT, value_parser = CountdownFormat::parse)]
+    format: CountdownFormat,
+    /// Fetch the sun times again instead of reusing today's cached ones
+    #[arg(long, requires = "lat")]
+    refresh: bool,
+    /// How often to try reaching the sunrise service, waiting twice as long after each failure
+    #[arg(long, value_name = "N", default_value_t = FETCH_ATTEMPTS, value_parser = clap::value_parser!(u32).range(1..), requires = "lat")]
+    fetch_attempts: u32,
+    /// Print the schedule for every 'YYYY-MM-DD H:M' sunrise line in this file and exit
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["sunrise", "coords", "lat", "lon"])]
+    week: Option<PathBuf>,
+    /// Play the alert and show the notification once, then exit
+    #[arg(long)]
+    test_alert: bool,
+    /// Print when an alert would fire instead of playing it
+    #[arg(long)]
+    dry_run: bool,
+    /// Only notify on alerts, without any sound or speech
+    #[arg(long, conflicts_with_all = ["tts", "persistent_alert"])]
+    no_audio: bool,
+    /// Ring the terminal bell instead of playing the alert sound
+    #[arg(long, conflicts_with_all = ["no_audio", "persistent_alert"])]
+    bell: bool,
+    /// Earliest sunrise used for the schedule, the sunrise is clamped to it
+    #[arg(long, value_name = "H:M", value_parser = parse_time_arg)]
+    min_wakeup: Option<NaiveTime>,
+    /// Latest sunrise used for the schedule, the sunrise is clamped to it
+    #[arg(long, value_name = "H:M", value_parser = parse_time_arg)]
+    max_wakeup: Option<NaiveTime>,
+    /// Use the raw sunrise instead of clamping it, clamping is on by default
+    #[arg(long, conflicts_with_all = ["min_wakeup", "max_wakeup"])]
+    no_clamp: bool,
+    /// Append a line to this file every time an alert fires
+    #[arg(long, value_name = "PATH")]
+    log: Option<PathBuf>,
+    /// POST the event as JSON to this URL whenever an alert fires
+    #[arg(long, value_name = "URL")]
+    webhook: Option<String>,
+    /// MQTT broker (host:port) to publish the upcoming event to
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "URL", requires = "mqtt_topic")]
+    mqtt_broker: Option<String>,
+    /// MQTT topic the upcoming event is published to
+    #[cfg(feature = "mqtt")]
+    #[arg(long, value_name = "TOPIC", requires = "mqtt_broker")]
+    mqtt_topic: Option<String>,
+    /// Answer GET /schedule and GET /next on this local port
+    #[cfg(feature = "serve")]
+    #[arg(long, value_name = "PORT")]
+    serve: Option<u16>,
+    /// Show times as %I:%M %p (9:47 PM) instead of %H:%M
+    #[arg(long = "12h")]
+    twelve_hour: bool,
+    /// Show times with seconds as %H:%M:%S (21:47:12)
+    #[arg(long)]
+    seconds: bool,
+    /// IANA time zone (America/New_York) for all times instead of the system zone
+    #[arg(long, value_name = "ZONE")]
+    tz: Option<chrono_tz::Tz>,
+    /// Latitude used to fetch the sunrise from sunrise-sunset.org
+    #[arg(long, requires = "lon", allow_negative_numbers = true)]
+    lat: Option<f64>,
+    /// Longitude used to fetch the sunrise from sunrise-sunset.org
+    #[arg(long, requires = "lat", allow_negative_numbers = true)]
+    lon: Option<f64>,
+}
+
+impl Cli {
+    fn zone(&self) -> Zone {
+        Zone(self.tz)
+    }
+    fn parse_with_event_help() -> Cli {
+        let matches = Cli::command()
+            // PROMPTY_SUNRISE or a pipe can stand in for the positional sunrise.
+            .arg_required_else_help(std::io::stdin().is_terminal() && env_sunrise().is_none())
+            .after_help(format!(
+                "Events (defaults, overridable in ~/.config/prompty/config.toml):\n  \
+                 Wake up time:   sunrise -{}m\n  \
+                 Lunch:          sunrise +{}h\n  \
+                 Solar noon:     halfway to sunset, else sunrise +{}h\n  \
+                 Evening dinner: sunrise +{}h\n  \
+                 Screens off:    bed time -{}m\n  \
+                 Bed time:       sunrise +{}h\n  \
+                 Golden hour:    sunset -{}m, with a known sunset\n\n\
+                 By default the sunrise is clamped between {} and {}, \
+                 see --min-wakeup/--max-wakeup and --no-clamp.\n\n\
+                 Exit codes:\n  \
+                 0 stopped with Ctrl-C or done\n  \
+                 1 any other failure\n  \
+                 {} bad input\n  \
+                 {} no audio device for --test-alert\n  \
+                 {} invalid config",
+                SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES,
+                LUNCH_TIME_SINCE_SUNRISE_IN_HOURS,
+                SOLAR_NOON_SINCE_SUNRISE_IN_HOURS,
+                DINNER_TIME_SINCE_SUNRISE_IN_HOURS,
+                SCREENS_OFF_BEFORE_BED_IN_MINUTES,
+                SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS,
+                GOLDEN_HOUR_BEFORE_SUNSET_IN_MINUTES,
+                MIN_WAKEUP_TIME,
+                MAX_WAKEUP_TIME,
+                EXIT_BAD_INPUT,
+                EXIT_AUDIO_UNAVAILABLE,
+                EXIT_INVALID_CONFIG
+            ))
+            .get_matches();
+        Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit())
     }
 }
 
-struct Timestamps {
-    wake_up_time: TimestampType,
-    bed_time: TimestampType,
-    dinner_time: TimestampType,
+fn parse_coords(value: &str) -> Result<(f64, f64), String> {
+    let (lat, lon) = value
+        .split_once(',')
+        .ok_or("Expected LAT,LON (52.5,13.4)")?;
+    match (lat.trim().parse(), lon.trim().parse()) {
+        (Ok(lat), Ok(lon)) => Ok((lat, lon)),
+        _ => Err(String::from("Expected LAT,LON as numbers (52.5,13.4)")),
+    }
 }
 
-impl Timestamps {
-    fn new() -> Timestamps {
-        let sunrise = gather_input();
-        Timestamps {
-            wake_up_time: TimestampType::WakeUpTime(
-                sunrise
-                    .overflowing_sub_signed(chrono::Duration::minutes(
-                        SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES,
-                    ))
-                    .0,
-            ),
-            dinner_time: TimestampType::DinnerTime(
-                sunrise
-                    .overflowing_add_signed(chrono::Duration::seconds(
-                        (DINNER_TIME_SINCE_SUNRISE_IN_HOURS * 3600.0) as i64,
-                    ))
-                    .0,
+enum InputError {
+    WrongArgumentCount,
+    UnparseableTime(String),
+    TimeOutOfRange(String),
+    InvertedWakeupBounds(NaiveTime, NaiveTime),
+    UnknownEvent(String),
+    OutOfOrder(Vec<String>),
+    Fetch(FetchError),
+}
+
+impl std::fmt::Display for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::WrongArgumentCount => write!(f, "Wrong number of arguments."),
+            InputError::UnparseableTime(input) => {
+                write!(
+                    f,
+                    "Could not read '{}' as a time. Expected %H:%M (9:47), %H:%M:%S (9:47:12) or %I:%M %p (9:47 AM).",
+                    input
+                )
+            }
+            InputError::TimeOutOfRange(input) => match out_of_range_part(input) {
+                Some(part) => write!(f, "'{}' is not a valid time of day, {}.", input, part),
+                None => write!(f, "'{}' is not a valid time of day.", input),
+            },
+            InputError::InvertedWakeupBounds(min, max) => write!(
+                f,
+                "The earliest wake up time {} is after the latest {}.",
+                format_time(*min),
+                format_time(*max)
             ),
-            bed_time: TimestampType::BedTime(
-                sunrise
-                    .overflowing_add_signed(chrono::Duration::seconds(
-                        (SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS * 3600.0) as i64,
-                    ))
-                    .0,
+            InputError::UnknownEvent(name) => write!(
+                f,
+                "There is no event called '{}', expected wake, lunch, noon, dinner, screens, bed, golden or a custom label.",
+                name
             ),
+            InputError::OutOfOrder(pairs) => {
+                write!(f, "The events are out of order: {}.", pairs.join(", "))
+            }
+            InputError::Fetch(e) => write!(f, "{:?}", e),
+        }?;
+        // Only a missing or unreadable sunrise is fixed by calling prompty differently.
+        match self {
+            InputError::WrongArgumentCount
+            | InputError::UnparseableTime(_)
+            | InputError::TimeOutOfRange(_) => write!(f, " {}", USAGE),
+            _ => Ok(()),
         }
     }
-    fn get_upcomming_timestamp(&self) -> &TimestampType {
-        let now = Local::now().time();
-        let mut upcomming_timestamp = &self.bed_time;
-        if let TimestampType::DinnerTime(value) = self.dinner_time {
-            if chrono::Duration::seconds(1).cmp(&now.signed_duration_since(value))
-                == Ordering::Greater
-            {
-                upcomming_timestamp = &self.dinner_time;
+}
+
+impl std::fmt::Debug for InputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for InputError {}
+
+// Names the field that made a well formed time invalid, "6:75" has no minute 75.
+fn out_of_range_part(input: &str) -> Option<String> {
+    let twelve_hour = input.to_lowercase().contains('m');
+    let hours = if twelve_hour { 1..=12 } else { 0..=23 };
+    input
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|part| !part.is_empty())
+        .zip([("hour", hours), ("minute", 0..=59), ("second", 0..=59)])
+        .find_map(|(part, (name, range))| {
+            let value: u32 = part.parse().ok()?;
+            (!range.contains(&value)).then(|| {
+                format!(
+                    "the {} {} is not within {}-{}",
+                    name,
+                    value,
+                    range.start(),
+                    range.end()
+                )
+            })
+        })
+}
+
+fn parse_time(input: &str) -> Result<NaiveTime, InputError> {
+    let mut error = InputError::UnparseableTime(input.to_string());
+    for time_format in SUNRISE_FORMATS {
+        match NaiveTime::parse_from_str(input, time_format) {
+            Ok(time) => return Ok(time),
+            Err(e) if e.kind() == format::ParseErrorKind::OutOfRange => {
+                error = InputError::TimeOutOfRange(input.to_string())
             }
+            Err(_) => {}
         }
-        if let TimestampType::WakeUpTime(value) = self.wake_up_time {
-            if chrono::Duration::seconds(1).cmp(&now.signed_duration_since(value))
-                == Ordering::Greater
-            {
-                upcomming_timestamp = &self.wake_up_time;
+    }
+    Err(error)
+}
+
+// --at on the day the schedule is for, a dated sunrise previews that day.
+fn preview_moment(timestamps: &Timestamps, at: NaiveTime, zone: Zone) -> NaiveDateTime {
+    timestamps
+        .date
+        .unwrap_or_else(|| zone.now().date())
+        .and_time(at)
+}
+
+fn parse_time_arg(value: &str) -> Result<NaiveTime, String> {
+    parse_time(value).map_err(|_| String::from("Expected %H:%M (6:30) or %I:%M %p (6:30 AM)"))
+}
+
+// The config file with the flags that replace its keys applied on top.
+fn effective_config(cli: &Cli, config: &Config) -> Config {
+    let mut config = config.clone();
+    if let Some(time) = cli.min_wakeup {
+        config.min_wakeup_time = time.format("%H:%M").to_string();
+    }
+    if let Some(time) = cli.max_wakeup {
+        config.max_wakeup_time = time.format("%H:%M").to_string();
+    }
+    if !cli.lead.is_empty() {
+        config.alert_leads_in_minutes = Some(cli.lead.clone());
+    }
+    if let Some(volume) = cli.volume {
+        config.volume = volume;
+    }
+    if let Some(sound) = &cli.sound {
+        config.sound = Some(sound.clone());
+    }
+    if let Some(window) = cli.snooze_window {
+        config.snooze_window_in_seconds = window;
+    }
+    config.twelve_hour_clock |= cli.twelve_hour;
+    config
+}
+
+fn wakeup_bounds(cli: &Cli, config: &Config) -> Result<Option<(NaiveTime, NaiveTime)>, InputError> {
+    if cli.no_clamp {
+        return Ok(None);
+    }
+    let min_wakeup_time = match cli.min_wakeup {
+        Some(time) => time,
+        None => parse_time(&config.min_wakeup_time)?,
+    };
+    let max_wakeup_time = match cli.max_wakeup {
+        Some(time) => time,
+        None => parse_time(&config.max_wakeup_time)?,
+    };
+    if min_wakeup_time > max_wakeup_time {
+        return Err(InputError::InvertedWakeupBounds(
+            min_wakeup_time,
+            max_wakeup_time,
+        ));
+    }
+    Ok(Some((min_wakeup_time, max_wakeup_time)))
+}
+
+fn clamp_sunrise(sunrise: NaiveTime, bounds: Option<(NaiveTime, NaiveTime)>) -> NaiveTime {
+    match bounds {
+        Some((min_wakeup_time, max_wakeup_time)) => sunrise.clamp(min_wakeup_time, max_wakeup_time),
+        None => sunrise,
+    }
+}
+
+fn sun_times_for_location(
+    cli: &Cli,
+    date: NaiveDate,
+) -> Option<Result<(NaiveTime, NaiveTime), FetchError>> {
+    if let Some((lat, lon)) = cli.coords {
+        Some(Ok((
+            compute_sunrise(lat, lon, date, cli.zone()),
+            compute_sunset(lat, lon, date, cli.zone()),
+        )))
+    } else if let (Some(lat), Some(lon)) = (cli.lat, cli.lon) {
+        // Watching is pointless if every recomputation returns the cached times.
+        Some(fetch_sun_times(
+            lat,
+            lon,
+            date,
+            cli.zone(),
+            cli.refresh || cli.watch.is_some(),
+            cli.fetch_attempts,
+        ))
+    } else {
+        None
+    }
+}
+
+// A leading date pins the schedule to that day, a bare time applies to today.
+fn parse_sunrise(input: &str) -> Result<(NaiveTime, Option<NaiveDate>), InputError> {
+    if !input.contains('-') {
+        return Ok((parse_time(input)?, None));
+    }
+    let unparseable = || InputError::UnparseableTime(input.to_string());
+    let (date, time) = input
+        .split_once(char::is_whitespace)
+        .ok_or_else(unparseable)?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| unparseable())?;
+    // The time keeps its own error, so "6:75" still names the minute that is off.
+    Ok((parse_time(time.trim())?, Some(date)))
+}
+
+const SUNRISE_ENV: &str = "PROMPTY_SUNRISE";
+
+fn env_sunrise() -> Option<String> {
+    std::env::var(SUNRISE_ENV)
+        .ok()
+        .filter(|sunrise| !sunrise.trim().is_empty())
+}
+
+// An explicit sunrise wins over PROMPTY_SUNRISE, which wins over stdin.
+fn gather_input(
+    cli: &Cli,
+) -> Result<(NaiveTime, Option<NaiveTime>, Option<NaiveDate>), InputError> {
+    let (sunrise, sunset, date) =
+        if let Some(sun_times) = sun_times_for_location(cli, cli.zone().now().date()) {
+            let (sunrise, sunset) = sun_times.map_err(InputError::Fetch)?;
+            (sunrise, Some(sunset), None)
+        } else if let Some(sunrise) = &cli.sunrise {
+            let (sunrise, date) = parse_sunrise(sunrise.replace('\n', "").trim())?;
+            (sunrise, None, date)
+        } else if let Some(sunrise) = env_sunrise() {
+            let (sunrise, date) = parse_sunrise(sunrise.trim())?;
+            (sunrise, None, date)
+        } else if !std::io::stdin().is_terminal() {
+            let mut line = String::new();
+            std::io::stdin()
+                .read_line(&mut line)
+                .map_err(|_| InputError::WrongArgumentCount)?;
+            let (sunrise, date) = parse_sunrise(line.trim())?;
+            (sunrise, None, date)
+        } else {
+            return Err(InputError::WrongArgumentCount);
+        };
+    Ok((sunrise, sunset, date))
+}
+
+struct AlertSettings {
+    sound: Option<PathBuf>,
+    // Keyed by the lowercase event name, like the leads per event.
+    sounds: BTreeMap<String, PathBuf>,
+    volume: f32,
+    persistent: bool,
+    tts: bool,
+    dry_run: bool,
+    no_audio: bool,
+    bell: bool,
+    jsonl: bool,
+    log: Option<std::io::BufWriter<std::fs::File>>,
+    zone: Zone,
+    webhook: Option<String>,
+    snooze: chrono::Duration,
+    snooze_window: std::time::Duration,
+    fade_out: f64,
+    #[cfg(feature = "mqtt")]
+    mqtt: Option<mqtt::Publisher>,
+}
+
+const TTS_COMMANDS: [&str; 3] = ["espeak", "say", "spd-say"];
+
+fn speak(text: &str) -> bool {
+    TTS_COMMANDS.iter().any(|command| {
+        std::process::Command::new(command)
+            .arg(text)
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false)
+    })
+}
+
+fn announcement(label: &str, lead: chrono::Duration) -> String {
+    match lead.num_minutes() {
+        0 => format!("{} now", label),
+        1 => format!("{} in one minute", label),
+        minutes => format!("{} in {} minutes", label, minutes),
+    }
+}
+
+fn spoken_summary(timestamps: &Timestamps) -> String {
+    let parts: Vec<String> = timestamps
+        .events
+        .iter()
+        .map(|event| {
+            format!(
+                "{} {}",
+                timestamps.get_summary_label(event),
+                format_time(event.get_naive_time())
+            )
+        })
+        .collect();
+    format!("{}.", parts.join(", "))
+}
+
+const EARLY_WARNING_VOLUME: f32 = 0.5;
+const PLAYBACK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+struct AudioUnavailable;
+
+impl std::fmt::Display for AudioUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "No audio device could be opened")
+    }
+}
+
+impl std::fmt::Debug for AudioUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl std::error::Error for AudioUnavailable {}
+
+// A file given with --log, --week or --ical.
+enum FileError {
+    Unreadable(PathBuf, std::io::Error),
+    Unwritable(PathBuf, std::io::Error),
+}
+
+impl std::fmt::Display for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileError::Unreadable(path, e) => write!(f, "Could not read {}: {}", path.display(), e),
+            FileError::Unwritable(path, e) => {
+                write!(f, "Could not write {}: {}", path.display(), e)
             }
         }
-        upcomming_timestamp
     }
-    fn get_abs_time_diff(&self, first: NaiveTime, second: NaiveTime) -> chrono::Duration {
-        second.signed_duration_since(first)
+}
+
+impl std::fmt::Debug for FileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self)
     }
 }
 
-fn gather_input() -> NaiveTime {
-    let min_wakeup_time = NaiveTime::parse_from_str(MIN_WAKEUP_TIME, "%H:%M").unwrap();
-    let max_wakeup_time = NaiveTime::parse_from_str(MAX_WAKEUP_TIME, "%H:%M").unwrap();
-    if env::args().len() == 2 {
-        NaiveTime::parse_from_str(
-            env::args().collect::<Vec<String>>()[1]
-                .replace("\n", "")
-                .as_str(),
-            "%H:%M",
-        )
-        .expect("Wrong parameter. Expected %H:%M (9:47) as first arg.")
-        .clamp(min_wakeup_time, max_wakeup_time)
-    } else {
-        panic!("Wrong time format. Expected %H:%M (9:47) as first arg.")
+impl std::error::Error for FileError {}
+
+struct AudioEngine {
+    // Declared before `wav` so the engine stops before the clip it plays is freed.
+    soloud: Option<Soloud>,
+    wav: Option<audio::Wav>,
+    // Sounds per event by path, `None` once a path failed to load.
+    sounds: BTreeMap<PathBuf, Option<audio::Wav>>,
+    unavailable: bool,
+    // The voice to fade out, when to start and how long the fade takes.
+    fade: Option<(Handle, std::time::Instant, std::time::Duration)>,
+}
+
+fn load_sound(path: &Path) -> Option<audio::Wav> {
+    let mut wav = audio::Wav::default();
+    match wav.load(path) {
+        Ok(()) => Some(wav),
+        Err(e) => {
+            eprintln!(
+                "\nWarning: could not load {} ({:?}), using the default sound",
+                path.display(),
+                e
+            );
+            None
+        }
     }
 }
 
-fn alert() {
-    let sl = Soloud::default().unwrap();
+fn load_wav(settings: &AlertSettings) -> Result<audio::Wav, SoloudError> {
+    if let Some(wav) = settings.sound.as_deref().and_then(load_sound) {
+        return Ok(wav);
+    }
     let mut wav = audio::Wav::default();
-    wav.load_mem(include_bytes!("../audio.mp3")).unwrap();
-    sl.play(&wav);
+    wav.load_mem(include_bytes!(env!("PROMPTY_AUDIO_PATH")))?;
+    Ok(wav)
 }
 
-fn countdown_next_events(timestamps: Timestamps) {
-    loop {
-        let upcomming = timestamps.get_upcomming_timestamp();
-        let now = Local::now().time();
-        let diff_to_upcomming = timestamps.get_abs_time_diff(now, upcomming.get_naive_time());
-        if diff_to_upcomming.num_hours() == 0
-            && diff_to_upcomming.num_minutes() == 10
-            && diff_to_upcomming.num_seconds() == 0
+impl AudioEngine {
+    fn new() -> AudioEngine {
+        AudioEngine {
+            soloud: None,
+            wav: None,
+            sounds: BTreeMap::new(),
+            unavailable: false,
+            fade: None,
+        }
+    }
+    /// The engine and `sound`, or the default clip when there is none or it cannot be loaded.
+    fn get(
+        &mut self,
+        settings: &AlertSettings,
+        sound: Option<&Path>,
+    ) -> Option<(&mut Soloud, &audio::Wav)> {
+        if self.soloud.is_none() && !self.unavailable {
+            match Soloud::default().and_then(|sl| Ok((sl, load_wav(settings)?))) {
+                Ok((mut sl, wav)) => {
+                    sl.set_global_volume(settings.volume);
+                    self.soloud = Some(sl);
+                    self.wav = Some(wav);
+                }
+                Err(e) => {
+                    // Warned once, later alerts stay silent instead of retrying.
+                    eprintln!("\n[audio unavailable] {:?}", e);
+                    self.unavailable = true;
+                }
+            }
+        }
+        if let (Some(_), Some(path)) = (&self.soloud, sound) {
+            if !self.sounds.contains_key(path) {
+                self.sounds.insert(path.to_path_buf(), load_sound(path));
+            }
+        }
+        let wav = sound
+            .and_then(|path| self.sounds.get(path))
+            .and_then(Option::as_ref)
+            .or(self.wav.as_ref());
+        match (&mut self.soloud, wav) {
+            (Some(sl), Some(wav)) => Some((sl, wav)),
+            _ => None,
+        }
+    }
+    fn wait_until_silent(&mut self) {
+        let started = std::time::Instant::now();
+        // A broken clip must not keep the process from ever exiting.
+        while self.soloud.as_ref().is_some_and(|sl| sl.voice_count() > 0)
+            && started.elapsed() < PLAYBACK_TIMEOUT
         {
-            alert();
-            std::thread::sleep(std::time::Duration::from_secs(10));
+            self.fade_if_due();
+            std::thread::sleep(std::time::Duration::from_millis(100));
         }
-        print!(
-            "{}",
+    }
+    fn until_fade(&self) -> Option<std::time::Duration> {
+        self.fade
+            .map(|(_, at, _)| at.saturating_duration_since(std::time::Instant::now()))
+    }
+    // Soloud fades start right away, so the fade is started once its time has come.
+    fn fade_if_due(&mut self) {
+        let (Some(sl), Some((handle, at, length))) = (&self.soloud, self.fade) else {
+            return;
+        };
+        if std::time::Instant::now() >= at {
+            // Shortened when started late, the voice is stopped on schedule either way.
+            let remaining = length.saturating_sub(at.elapsed());
+            sl.fade_volume(handle, 0.0, remaining.as_secs_f64());
+            self.fade = None;
+        }
+    }
+}
+
+const BELL_RINGS: u32 = 3;
+
+// Reaches the user through the terminal, even over SSH.
+fn ring_bell() {
+    for ring in 0..BELL_RINGS {
+        if ring > 0 {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+        }
+        print!("\x07");
+        std::io::stdout().flush().unwrap();
+    }
+}
+
+// Without an audio device the alert falls back to the system sound where there is one,
+// the notification has already been posted either way.
+#[cfg(windows)]
+fn fallback_alert() {
+    use windows_sys::Win32::System::Diagnostics::Debug::MessageBeep;
+    use windows_sys::Win32::UI::WindowsAndMessaging::MB_ICONASTERISK;
+    // SAFETY: MessageBeep takes no pointers and only queues the sound.
+    unsafe {
+        MessageBeep(MB_ICONASTERISK);
+    }
+}
+
+#[cfg(not(windows))]
+fn fallback_alert() {
+    ring_bell();
+}
+
+fn alert(engine: &mut AudioEngine, settings: &AlertSettings, event: &TimestampType, early: bool) {
+    if settings.bell {
+        ring_bell();
+        return;
+    }
+    let sound = settings.sounds.get(&event.get_name().to_lowercase());
+    let Some((sl, wav)) = engine.get(settings, sound.map(PathBuf::as_path)) else {
+        // The fallback bell is written to stdout, into the stream of JSON lines.
+        if !settings.jsonl {
+            fallback_alert();
+        }
+        return;
+    };
+    let handle = sl.play(wav);
+    if early {
+        sl.set_volume(handle, EARLY_WARNING_VOLUME);
+    }
+    if settings.persistent {
+        sl.set_looping(handle, true);
+        print!("\n Alert! Press Enter to dismiss");
+        std::io::stdout().flush().unwrap();
+        let _ = std::io::stdin().read_line(&mut String::new());
+        sl.stop_all();
+        return;
+    }
+    // The fade happens within the clip, so playback never runs past its cap.
+    let length = wav.length().min(PLAYBACK_TIMEOUT.as_secs_f64());
+    sl.schedule_stop(handle, length);
+    let fade = settings.fade_out.min(length);
+    if fade > 0.0 {
+        engine.fade = Some((
+            handle,
+            std::time::Instant::now() + std::time::Duration::from_secs_f64(length - fade),
+            std::time::Duration::from_secs_f64(fade),
+        ));
+    }
+}
+
+fn post_webhook(url: &str, event: &TimestampType) {
+    let body = serde_json::json!({
+        "event": event.get_name(),
+        "time": event.get_naive_time().format("%H:%M").to_string(),
+    });
+    if let Err(e) = ureq::post(url)
+        .timeout(std::time::Duration::from_secs(5))
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+    {
+        log::warn!("Webhook {} failed: {}", url, e);
+    }
+}
+
+fn notify(event: &TimestampType, label: &str) {
+    let _ = notify_rust::Notification::new()
+        .summary("prompty")
+        .body(&format!(
+            "{} at {}",
+            label,
+            format_time(event.get_naive_time())
+        ))
+        .show();
+}
+
+struct DisplaySettings {
+    all: bool,
+    quiet: bool,
+    interval: std::time::Duration,
+    watch: Option<std::time::Duration>,
+    format: CountdownFormat,
+    exit_after_alert: bool,
+    bar: bool,
+    since: bool,
+    until: bool,
+    summary: bool,
+    clock: bool,
+    jsonl: bool,
+}
+
+const BAR_WIDTH: usize = 30;
+
+// A wrapped line cannot be overwritten by the next redraw, so it is cut at the terminal width.
+fn fit_to_terminal(line: &str) -> String {
+    // Pseudo terminals without a size report zero columns.
+    match terminal::size()
+        .ok()
+        .map(|(width, _)| width)
+        .filter(|width| *width > 0)
+    {
+        // Filling the last column leaves some terminals waiting to wrap.
+        Some(width) => fit_to_width(line, (width as usize).saturating_sub(1)),
+        None => line.to_string(),
+    }
+}
+
+// Escape sequences take no room and are kept, styles are reset after a cut.
+// Wide glyphs like the phase emoji take two columns.
+fn fit_to_width(line: &str, width: usize) -> String {
+    let mut fitted = String::new();
+    let mut visible = 0;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        let columns = c.width().unwrap_or(0);
+        if c == '\x1b' {
+            fitted.push(c);
+            for c in chars.by_ref() {
+                fitted.push(c);
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else if visible + columns <= width {
+            fitted.push(c);
+            visible += columns;
+        } else {
+            fitted.push_str("\x1b[0m");
+            break;
+        }
+    }
+    fitted
+}
+
+fn progress_bar(remaining: chrono::Duration, gap: chrono::Duration) -> String {
+    let elapsed = 1.0 - remaining.num_seconds() as f64 / gap.num_seconds().max(1) as f64;
+    let filled = ((elapsed.clamp(0.0, 1.0) * BAR_WIDTH as f64).round() as usize).min(BAR_WIDTH);
+    format!(
+        "[{}{}] {:>3}%",
+        "█".repeat(filled),
+        "░".repeat(BAR_WIDTH - filled),
+        (elapsed.clamp(0.0, 1.0) * 100.0).floor()
+    )
+}
+
+fn format_countdown(diff: chrono::Duration) -> String {
+    format!(
+        "{:02}:{:02}:{:02}",
+        diff.num_hours(),
+        diff.num_minutes() % 60,
+        diff.num_seconds() % 60
+    )
+}
+
+fn bold(text: &str) -> String {
+    text.if_supports_color(Stdout, |text| text.bold())
+        .to_string()
+}
+
+fn colored_countdown(diff: chrono::Duration, lead: chrono::Duration) -> String {
+    colored(format_countdown(diff), diff, lead)
+}
+
+fn colored(countdown: String, diff: chrono::Duration, lead: chrono::Duration) -> String {
+    if diff <= lead {
+        countdown
+            .if_supports_color(Stdout, |text| text.red())
+            .to_string()
+    } else {
+        countdown
+            .if_supports_color(Stdout, |text| text.green())
+            .to_string()
+    }
+}
+
+const DEFAULT_COUNTDOWN_FORMAT: &str = "Upcomming event: '{label}' at {at} in {h}:{m}:{s}";
+
+#[derive(Clone)]
+enum FormatPiece {
+    Text(String),
+    Label,
+    At,
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+#[derive(Clone)]
+struct CountdownFormat(Vec<FormatPiece>);
+
+impl CountdownFormat {
+    fn parse(template: &str) -> Result<CountdownFormat, String> {
+        let mut pieces = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                pieces.push(FormatPiece::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("Unclosed '{{' in '{}'", template))?
+                + start;
+            pieces.push(match &rest[start + 1..end] {
+                "label" => FormatPiece::Label,
+                "at" => FormatPiece::At,
+                "h" => FormatPiece::Hours,
+                "m" => FormatPiece::Minutes,
+                "s" => FormatPiece::Seconds,
+                unknown => {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}', expected {{label}}, {{at}}, {{h}}, {{m}} or {{s}}",
+                        unknown
+                    ))
+                }
+            });
+            rest = &rest[end + 1..];
+        }
+        if !rest.is_empty() {
+            pieces.push(FormatPiece::Text(rest.to_string()));
+        }
+        Ok(CountdownFormat(pieces))
+    }
+    fn render(
+        &self,
+        event: &TimestampType,
+        label: &str,
+        diff: chrono::Duration,
+        lead: chrono::Duration,
+    ) -> String {
+        self.0
+            .iter()
+            .map(|piece| match piece {
+                FormatPiece::Text(text) => text.clone(),
+                FormatPiece::Label => bold(label),
+                FormatPiece::At => format_time(event.get_naive_time()),
+                FormatPiece::Hours => colored(format!("{:02}", diff.num_hours()), diff, lead),
+                FormatPiece::Minutes => {
+                    colored(format!("{:02}", diff.num_minutes() % 60), diff, lead)
+                }
+                FormatPiece::Seconds => {
+                    colored(format!("{:02}", diff.num_seconds() % 60), diff, lead)
+                }
+            })
+            .collect()
+    }
+}
+
+fn print_all_events(
+    timestamps: &Timestamps,
+    now: NaiveDateTime,
+    alert_leads: &[chrono::Duration],
+    redraw: bool,
+) {
+    if redraw {
+        print!("\x1b[{}A", timestamps.events.len());
+    }
+    for event in &timestamps.events {
+        let status = if timestamps.is_done(event, now) {
+            String::from("done")
+        } else {
             format!(
-                "\r Upcomming event: '{:?}' in {:02}:{:02}:{:02}                                           ",
-                upcomming,
-                diff_to_upcomming.num_hours(),
-                diff_to_upcomming.num_minutes() % 60,
-                diff_to_upcomming.num_seconds() % 60
+                "in {}",
+                colored_countdown(
+                    timestamps.get_time_until(event, now),
+                    timestamps.get_alert_leads(event, alert_leads)[0]
+                )
             )
+        };
+        println!(
+            "\r{}\x1b[K",
+            fit_to_terminal(&format!(
+                " {} {}  {}",
+                bold(&format!("{:<28}", timestamps.get_label(event))),
+                format_time(event.get_naive_time()),
+                status
+            ))
         );
-        std::io::stdout().flush().unwrap();
-        std::thread::sleep(std::time::Duration::from_secs(1));
     }
 }
 
-fn main() {
-    let timestamps = Timestamps::new();
+fn format_offset(offset: chrono::Duration) -> String {
+    let sign = if offset < chrono::Duration::zero() {
+        "-"
+    } else {
+        "+"
+    };
+    let offset = offset.abs();
+    if offset.num_seconds() % 3600 == 0 || offset.num_hours() > 0 {
+        format!("{} {}h", sign, offset.num_seconds() as f32 / 3600.0)
+    } else {
+        format!("{} {}m", sign, offset.num_minutes())
+    }
+}
 
-    print!(
-        " Wake up time:   {} (-{}m)\n Evening dinner: {} (+{}h)\n Bed time:       {} (+{}h)\n",
-        if let TimestampType::WakeUpTime(value) = timestamps.wake_up_time {
-            value.format("%H:%M")
-        } else {
-            format::DelayedFormat::new(None, None, format::StrftimeItems::new("moin"))
+fn print_derivations(
+    timestamps: &Timestamps,
+    measured_sunrise: NaiveTime,
+    bounds: Option<(NaiveTime, NaiveTime)>,
+) {
+    println!();
+    match bounds {
+        Some((min, max)) if measured_sunrise != timestamps.sunrise => println!(
+            " sunrise = {} clamped to {}..{} = {}",
+            format_time(measured_sunrise),
+            format_time(min),
+            format_time(max),
+            format_time(timestamps.sunrise)
+        ),
+        _ => println!(" sunrise = {}", format_time(timestamps.sunrise)),
+    }
+    for event in &timestamps.events {
+        let formula = match timestamps.get_derivation(event) {
+            Derivation::Offset { anchor, at, offset } => {
+                format!("{}({}) {}", anchor, format_time(at), format_offset(offset))
+            }
+            Derivation::Midpoint { sunrise, sunset } => format!(
+                "halfway from sunrise({}) to sunset({})",
+                format_time(sunrise),
+                format_time(sunset)
+            ),
+        };
+        println!(
+            " {} = {} = {}",
+            event.get_name(),
+            formula,
+            format_time(event.get_naive_time())
+        );
+    }
+}
+
+fn print_summary(timestamps: &Timestamps) {
+    let mut summary = String::new();
+    let _ = timestamps.write_summary(&mut summary, &format_time, &bold);
+    println!("{}", summary);
+}
+
+fn parse_dated_sunrise(line: &str) -> Result<(NaiveDate, NaiveTime), String> {
+    let (date, time) = line
+        .split_once(char::is_whitespace)
+        .ok_or("expected 'YYYY-MM-DD H:M'")?;
+    let date = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a YYYY-MM-DD date", date))?;
+    let time = parse_time(time.trim()).map_err(|_| format!("'{}' is not a time", time.trim()))?;
+    Ok((date, time))
+}
+
+fn print_week(content: &str, config: &Config, bounds: Option<(NaiveTime, NaiveTime)>) {
+    let mut rows = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_dated_sunrise(line.trim()) {
+            Ok((date, sunrise)) => rows.push((
+                date,
+                TimestampsBuilder::from_config(config)
+                    .sunrise(clamp_sunrise(sunrise, bounds))
+                    .date(Some(date))
+                    .build(),
+            )),
+            Err(e) => eprintln!("Skipping line {}: {}", index + 1, e),
+        }
+    }
+    let Some((_, first)) = rows.first() else {
+        return;
+    };
+    let ids: Vec<EventId> = first.events.iter().map(TimestampType::get_id).collect();
+    let mut header = format!(" {:<10}  {:<8}", "Date", "Sunrise");
+    for event in &first.events {
+        header.push_str(&format!("  {:<14}", first.get_summary_label(event)));
+    }
+    println!("{}", header.trim_end());
+    for (date, timestamps) in &rows {
+        let mut row = format!(
+            " {:<10}  {:<8}",
+            date.format("%Y-%m-%d"),
+            format_time(timestamps.sunrise)
+        );
+        for id in &ids {
+            let time = timestamps
+                .events
+                .iter()
+                .find(|e| &e.get_id() == id)
+                .map(|e| format_time(e.get_naive_time()))
+                .unwrap_or_default();
+            row.push_str(&format!("  {:<14}", time));
+        }
+        println!("{}", row.trim_end());
+    }
+}
+
+fn fire_alert(
+    event: &TimestampType,
+    label: &str,
+    lead: chrono::Duration,
+    early: bool,
+    engine: &mut AudioEngine,
+    settings: &mut AlertSettings,
+) {
+    log::info!("Alert for {:?}", event);
+    #[cfg(feature = "mqtt")]
+    if let Some(publisher) = &mut settings.mqtt {
+        publisher.publish(event);
+    }
+    if let Some(log) = &mut settings.log {
+        // Flushed right away so the trail survives a crash.
+        let _ = writeln!(
+            log,
+            "{} ALERT {}",
+            settings.zone.now().format("%Y-%m-%dT%H:%M:%S"),
+            event.get_name()
+        )
+        .and_then(|()| log.flush());
+    }
+    if settings.jsonl {
+        print_json_line(serde_json::json!({ "alert": event.get_name() }));
+    }
+    if settings.dry_run {
+        if !settings.jsonl {
+            println!("\n[would alert: {}]", label);
+        }
+        return;
+    }
+    if let Some(url) = &settings.webhook {
+        post_webhook(url, event);
+    }
+    notify(event, label);
+    // Returning before `alert` means the audio engine is never even opened.
+    if settings.no_audio {
+        return;
+    }
+    if !(settings.tts && speak(&announcement(label, lead))) {
+        alert(engine, settings, event, early);
+    }
+}
+
+// Once the reader is gone, like `head` after its lines, there is nobody left to count down for.
+fn print_json_line(line: serde_json::Value) {
+    let mut stdout = std::io::stdout().lock();
+    if writeln!(stdout, "{}", line)
+        .and_then(|()| stdout.flush())
+        .is_err()
+    {
+        std::process::exit(0);
+    }
+}
+
+fn stop() -> ! {
+    let _ = terminal::disable_raw_mode();
+    // On stderr, so it stays out of the JSON lines.
+    eprintln!("\n Stopped");
+    // Exiting also tears down the audio engine, silencing any alert still playing.
+    std::process::exit(0);
+}
+
+// Latched instead of matching the exact second, which a late tick can skip.
+// Several leads reached by the same tick only alert once, for the latest of them.
+// Returns which of `leads` is due with `until` left, `alerted` holds the leads reached so far.
+fn due_alert(
+    alerted: &mut Vec<bool>,
+    leads: &[chrono::Duration],
+    until: chrono::Duration,
+) -> Option<usize> {
+    if alerted.len() != leads.len() {
+        *alerted = vec![false; leads.len()];
+    }
+    let reached: Vec<bool> = leads
+        .iter()
+        .map(|lead| until.num_seconds() <= lead.num_seconds())
+        .collect();
+    let due = (0..leads.len())
+        .rev()
+        .find(|&index| reached[index] && !alerted[index]);
+    *alerted = reached;
+    due
+}
+
+const MAX_SNOOZES: u32 = 3;
+
+// Raw mode is only held while waiting, so everything printed in between keeps its line endings.
+fn poll_key(timeout: std::time::Duration) -> Option<KeyCode> {
+    if !std::io::stdin().is_terminal() || terminal::enable_raw_mode().is_err() {
+        std::thread::sleep(timeout);
+        return None;
+    }
+    let key = match event::poll(timeout) {
+        Ok(true) => match event::read() {
+            // Raw mode turns Ctrl-C into a key press instead of SIGINT.
+            Ok(Event::Key(key))
+                if key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                stop()
+            }
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => Some(key.code),
+            _ => None,
         },
-        SUNRISE_MODIFIER_FOR_WAKE_UP_TIME_IN_MINUTES,
-        if let TimestampType::DinnerTime(value) = timestamps.dinner_time {
-            value.format("%H:%M")
+        Ok(false) => None,
+        Err(_) => {
+            std::thread::sleep(timeout);
+            None
+        }
+    };
+    let _ = terminal::disable_raw_mode();
+    key
+}
+
+fn offer_snooze(
+    count: u32,
+    length: chrono::Duration,
+    until_event: chrono::Duration,
+    window: std::time::Duration,
+) -> Option<(std::time::Instant, u32)> {
+    // Snoozing past the event itself would alert for an event that already happened.
+    if count >= MAX_SNOOZES || length >= until_event || !std::io::stdin().is_terminal() {
+        return None;
+    }
+    print!(
+        "\n Press 's' within {}s to snooze for {} minutes\r\n",
+        window.as_secs(),
+        length.num_minutes()
+    );
+    Some((std::time::Instant::now() + window, count))
+}
+
+// Differences below a minute are rounding in the sun calculations rather than a new schedule.
+fn schedule_changed(old: &Timestamps, new: &Timestamps) -> bool {
+    old.events.len() != new.events.len()
+        || old.events.iter().zip(&new.events).any(|(old, new)| {
+            old != new
+                && (new.get_naive_time() - old.get_naive_time())
+                    .num_seconds()
+                    .abs()
+                    >= 60
+        })
+}
+
+const ADJUST_STEP_IN_MINUTES: i64 = 5;
+
+fn countdown_next_events(
+    mut timestamps: Timestamps,
+    reschedule: &dyn Fn() -> Timestamps,
+    clock: &dyn Clock,
+    alert_leads: &[chrono::Duration],
+    mut alert_settings: AlertSettings,
+    display_settings: DisplaySettings,
+    served: Option<&Mutex<Timestamps>>,
+) {
+    let mut audio_engine = AudioEngine::new();
+    let mut alerted = vec![false; alert_leads.len()];
+    let mut snooze = None;
+    let mut snooze_offer: Option<(std::time::Instant, u32)> = None;
+    let mut redraw = false;
+    let mut previous_now = clock.now_date_time();
+    let mut paused_at: Option<NaiveDateTime> = None;
+    let mut last_watch = std::time::Instant::now();
+    // Compared against when watching, so events moved with +/- are not undone.
+    let mut computed = timestamps.clone();
+    // When the --until event happens next, kept as a moment so it survives the rollover.
+    // Started after the event already passed today, --until waits for tomorrow's.
+    let mut until_at: Option<NaiveDateTime> = None;
+    loop {
+        // A paused countdown keeps showing the moment it was paused and cannot reach a lead.
+        // Leads passed meanwhile still alert once it resumes, like after a late tick.
+        let now = paused_at.unwrap_or_else(|| clock.now_date_time());
+        let rolled_over = now.date() != previous_now.date();
+        let watch_due = display_settings
+            .watch
+            .is_some_and(|watch| last_watch.elapsed() >= watch);
+        if rolled_over || watch_due {
+            let rescheduled = reschedule();
+            last_watch = std::time::Instant::now();
+            if rolled_over || schedule_changed(&computed, &rescheduled) {
+                computed = rescheduled.clone();
+                timestamps = rescheduled;
+                if display_settings.summary {
+                    if !display_settings.all && !display_settings.quiet {
+                        println!();
+                    }
+                    print_summary(&timestamps);
+                    redraw = false;
+                }
+            }
+        }
+        // Events moved with +/- reach the server a tick later, like the summary.
+        if let Some(served) = served {
+            *served.lock().unwrap_or_else(|e| e.into_inner()) = timestamps.clone();
+        }
+        previous_now = now;
+        let Some(upcomming) = timestamps.get_upcomming_timestamp_on(now) else {
+            return;
+        };
+        let diff_to_upcomming = timestamps.get_time_until(upcomming, now);
+        let leads = timestamps.get_alert_leads(upcomming, alert_leads);
+        let lead = leads[0];
+        log::debug!(
+            "Upcomming {:?} in {}s, alert leads {:?}s",
+            upcomming,
+            diff_to_upcomming.num_seconds(),
+            leads
+                .iter()
+                .map(|lead| lead.num_seconds())
+                .collect::<Vec<_>>()
+        );
+        #[cfg(feature = "mqtt")]
+        if let Some(publisher) = &mut alert_settings.mqtt {
+            publisher.publish_if_changed(upcomming);
+        }
+        if diff_to_upcomming.num_seconds() > leads[leads.len() - 1].num_seconds() {
+            snooze = None;
+        }
+        if let Some(index) = due_alert(&mut alerted, &leads, diff_to_upcomming) {
+            let early = index + 1 < leads.len();
+            fire_alert(
+                upcomming,
+                &timestamps.get_label(upcomming),
+                diff_to_upcomming,
+                early,
+                &mut audio_engine,
+                &mut alert_settings,
+            );
+            if !early && display_settings.exit_after_alert {
+                audio_engine.wait_until_silent();
+                return;
+            }
+            // A stream has nobody at the keyboard to snooze.
+            if !early && !display_settings.jsonl {
+                snooze_offer = offer_snooze(
+                    0,
+                    alert_settings.snooze,
+                    diff_to_upcomming,
+                    alert_settings.snooze_window,
+                );
+            }
+            redraw = false;
+        } else if let Some((until, count)) = snooze {
+            if paused_at.is_none() && std::time::Instant::now() >= until {
+                fire_alert(
+                    upcomming,
+                    &timestamps.get_label(upcomming),
+                    diff_to_upcomming,
+                    false,
+                    &mut audio_engine,
+                    &mut alert_settings,
+                );
+                snooze = None;
+                snooze_offer = offer_snooze(
+                    count,
+                    alert_settings.snooze,
+                    diff_to_upcomming,
+                    alert_settings.snooze_window,
+                );
+                redraw = false;
+            }
+        }
+        if display_settings.until {
+            if until_at.is_some_and(|at| now >= at) {
+                audio_engine.wait_until_silent();
+                if !display_settings.jsonl {
+                    println!();
+                }
+                return;
+            }
+            let until = timestamps.get_time_until(upcomming, now);
+            if until > chrono::Duration::zero() {
+                until_at = Some(now + until);
+            }
+        }
+        let mut suffix = String::new();
+        if display_settings.since && !timestamps.is_pending(now) {
+            // Before the first event of the day this is the last one of the prior day.
+            if let Some(previous) = timestamps.get_previous_timestamp(upcomming) {
+                suffix.push_str(&format!(
+                    " (since {} {})",
+                    timestamps.get_summary_label(previous),
+                    format_countdown(
+                        timestamps.get_abs_time_diff(previous.get_naive_time(), now.time())
+                    )
+                ));
+            }
+        }
+        if paused_at.is_some() {
+            suffix.push_str(" [paused]");
+        }
+        // The real time, which keeps running while the countdown is paused.
+        let prefix = if display_settings.clock {
+            format!("[{}] ", format_clock(clock.now()))
         } else {
-            format::DelayedFormat::new(None, None, format::StrftimeItems::new("moin"))
+            String::new()
+        };
+        if display_settings.jsonl {
+            print_json_line(serde_json::json!({
+                "now": now.format("%H:%M:%S").to_string(),
+                "event": upcomming.get_name(),
+                "remaining_secs": diff_to_upcomming.num_seconds(),
+            }));
+        } else if display_settings.all && !display_settings.quiet {
+            print_all_events(&timestamps, now, alert_leads, redraw);
+            redraw = true;
+        } else if !display_settings.quiet && display_settings.bar {
+            print!(
+                "\r{}\x1b[K",
+                fit_to_terminal(&format!(
+                    " {}{} {}{}",
+                    prefix,
+                    progress_bar(diff_to_upcomming, timestamps.get_gap_before(upcomming)),
+                    display_settings.format.render(
+                        upcomming,
+                        &timestamps.get_label(upcomming),
+                        diff_to_upcomming,
+                        lead,
+                    ),
+                    suffix
+                ))
+            );
+            std::io::stdout().flush().unwrap();
+        } else if !display_settings.quiet {
+            print!(
+                "\r{}\x1b[K",
+                fit_to_terminal(&format!(
+                    " {}{}{}",
+                    prefix,
+                    display_settings.format.render(
+                        upcomming,
+                        &timestamps.get_label(upcomming),
+                        diff_to_upcomming,
+                        lead,
+                    ),
+                    suffix
+                ))
+            );
+            std::io::stdout().flush().unwrap();
+        }
+        let key = poll_key(
+            audio_engine
+                .until_fade()
+                .map_or(display_settings.interval, |until| {
+                    until.min(display_settings.interval)
+                }),
+        );
+        audio_engine.fade_if_due();
+        if key == Some(KeyCode::Char(' ')) {
+            paused_at = match paused_at {
+                Some(_) => None,
+                None => Some(now),
+            };
+        }
+        if let Some(KeyCode::Char(sign @ ('+' | '-'))) = key {
+            let step = chrono::Duration::minutes(ADJUST_STEP_IN_MINUTES);
+            // Moved into the past the event would be skipped rather than alerted for.
+            let by = match sign {
+                '+' => step,
+                _ => -step.min(
+                    (diff_to_upcomming - chrono::Duration::minutes(1))
+                        .max(chrono::Duration::zero()),
+                ),
+            };
+            let event = upcomming.clone();
+            // The alert latches follow the new lead times on the next tick.
+            if !timestamps.adjust_event(&event, by).is_zero() && display_settings.summary {
+                if !display_settings.all && !display_settings.quiet {
+                    println!();
+                }
+                print_summary(&timestamps);
+                redraw = false;
+            }
+        }
+        match snooze_offer {
+            Some((until, _)) if std::time::Instant::now() >= until => snooze_offer = None,
+            Some((_, count)) if key == Some(KeyCode::Char('s')) => {
+                println!("\n Snoozed ({}/{})", count + 1, MAX_SNOOZES);
+                snooze = alert_settings
+                    .snooze
+                    .to_std()
+                    .ok()
+                    .map(|length| (std::time::Instant::now() + length, count + 1));
+                snooze_offer = None;
+                redraw = false;
+            }
+            _ => {}
+        }
+    }
+}
+
+const EXIT_BAD_INPUT: u8 = 2;
+const EXIT_AUDIO_UNAVAILABLE: u8 = 3;
+const EXIT_INVALID_CONFIG: u8 = 4;
+
+fn exit_code(error: &(dyn std::error::Error + 'static)) -> u8 {
+    if error.is::<InputError>() {
+        EXIT_BAD_INPUT
+    } else if error.is::<AudioUnavailable>() {
+        EXIT_AUDIO_UNAVAILABLE
+    } else if error.is::<ConfigError>() {
+        EXIT_INVALID_CONFIG
+    } else {
+        1
+    }
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code(e.as_ref()))
+        }
+    }
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+    // Older Windows consoles only understand the escape codes used to redraw once asked to.
+    #[cfg(windows)]
+    if !crossterm::ansi_support::supports_ansi() {
+        log::warn!("This console does not support ANSI escape codes");
+    }
+    ctrlc::set_handler(|| stop())?;
+    let cli = Cli::parse_with_event_help();
+    let config = Config::load(&cli.profile)?;
+    if cli.dump_config {
+        print!("{}", toml::to_string(&effective_config(&cli, &config))?);
+        return Ok(());
+    }
+    let clock = SystemClock(cli.zone());
+    match (cli.twelve_hour || config.twelve_hour_clock, cli.seconds) {
+        (true, true) => {
+            let _ = TIME_FORMAT.set(TWELVE_HOUR_SECONDS_FORMAT);
+        }
+        (true, false) => {
+            let _ = TIME_FORMAT.set(TWELVE_HOUR_FORMAT);
+        }
+        (false, true) => {
+            let _ = TIME_FORMAT.set(SECONDS_FORMAT);
+        }
+        (false, false) => {}
+    }
+    let mut alert_leads: Vec<chrono::Duration> = if cli.lead.is_empty() {
+        config
+            .alert_leads_in_minutes
+            .clone()
+            .filter(|leads| !leads.is_empty())
+            .unwrap_or(vec![config.alert_lead_in_minutes])
+    } else {
+        cli.lead.clone()
+    }
+    .into_iter()
+    .map(chrono::Duration::minutes)
+    .collect();
+    alert_leads.sort_by(|a, b| b.cmp(a));
+    alert_leads.dedup();
+    let volume = cli.volume.unwrap_or(config.volume);
+    if !(0.0..=1.0).contains(&volume) {
+        eprintln!(
+            "Warning: volume {} is outside 0.0..=1.0, clamping it",
+            volume
+        );
+    }
+    let mut alert_settings = AlertSettings {
+        sound: cli.sound.clone().or(config.sound.clone()),
+        sounds: config
+            .sounds
+            .iter()
+            .map(|(name, path)| (name.to_lowercase(), path.clone()))
+            .collect(),
+        volume: volume.clamp(0.0, 1.0),
+        persistent: cli.persistent_alert,
+        tts: cli.tts,
+        dry_run: cli.dry_run,
+        no_audio: cli.no_audio,
+        bell: cli.bell,
+        jsonl: cli.jsonl,
+        log: match &cli.log {
+            Some(path) => Some(std::io::BufWriter::new(
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| FileError::Unwritable(path.clone(), e))?,
+            )),
+            None => None,
         },
-        DINNER_TIME_SINCE_SUNRISE_IN_HOURS,
-        if let TimestampType::BedTime(value) = timestamps.bed_time {
-            value.format("%H:%M")
-        } else {
-            format::DelayedFormat::new(None, None, format::StrftimeItems::new("moin"))
+        webhook: cli.webhook.clone(),
+        zone: cli.zone(),
+        snooze: chrono::Duration::minutes(config.snooze_minutes),
+        snooze_window: std::time::Duration::from_secs(
+            cli.snooze_window.unwrap_or(config.snooze_window_in_seconds),
+        ),
+        fade_out: config.alert_fade_out_in_seconds.max(0.0) as f64,
+        #[cfg(feature = "mqtt")]
+        mqtt: match (&cli.mqtt_broker, &cli.mqtt_topic) {
+            (Some(broker), Some(topic)) => Some(mqtt::Publisher::connect(broker, topic)?),
+            _ => None,
         },
-        SUNRISE_MODIFIER_FOR_BED_TIME_IN_HOURS
+    };
+
+    if cli.test_alert {
+        let event = TimestampType::Custom(String::from("Test alert"), clock.now());
+        let mut audio_engine = AudioEngine::new();
+        fire_alert(
+            &event,
+            "Test alert",
+            alert_leads[alert_leads.len() - 1],
+            false,
+            &mut audio_engine,
+            &mut alert_settings,
+        );
+        audio_engine.wait_until_silent();
+        // The test alert exists to check the audio, so a silent one is a failure.
+        if audio_engine.unavailable {
+            return Err(AudioUnavailable.into());
+        }
+        return Ok(());
+    }
+
+    let bounds = wakeup_bounds(&cli, &config)?;
+    if let Some(path) = &cli.week {
+        let content =
+            std::fs::read_to_string(path).map_err(|e| FileError::Unreadable(path.clone(), e))?;
+        print_week(&content, &config, bounds);
+        return Ok(());
+    }
+    let (measured_sunrise, sunset, date) = gather_input(&cli)?;
+    let sunrise = clamp_sunrise(measured_sunrise, bounds);
+    if sunset.is_none() && config.uses_sunset() {
+        eprintln!(
+            "Warning: the sunset is only known with --coords or --lat/--lon, \
+             so bed time uses its sunrise offset and sunset-anchored events are skipped"
+        );
+    }
+    let mut timestamps = TimestampsBuilder::from_config(&config)
+        .sunrise(sunrise)
+        .measured_sunrise(measured_sunrise)
+        .sunset(sunset)
+        .date(date)
+        .zone(cli.zone())
+        .build();
+    if timestamps.events.is_empty() {
+        return Err(ConfigError::NoEnabledEvents.into());
+    }
+    let out_of_order: Vec<String> = timestamps
+        .get_out_of_order_events()
+        .iter()
+        .map(|(first, second)| {
+            format!(
+                "{} ({}) is not before {} ({})",
+                timestamps.get_summary_label(first),
+                format_time(first.get_naive_time()),
+                timestamps.get_summary_label(second),
+                format_time(second.get_naive_time())
+            )
+        })
+        .collect();
+    if !out_of_order.is_empty() {
+        if cli.strict {
+            return Err(InputError::OutOfOrder(out_of_order).into());
+        }
+        for pair in &out_of_order {
+            eprintln!("Warning: {}, check the offsets in your config", pair);
+        }
+    }
+    for (first, second) in
+        timestamps.get_close_events(chrono::Duration::minutes(config.min_event_gap_in_minutes))
+    {
+        eprintln!(
+            "Warning: {} ({}) and {} ({}) are less than {} minutes apart",
+            timestamps.get_summary_label(first),
+            format_time(first.get_naive_time()),
+            timestamps.get_summary_label(second),
+            format_time(second.get_naive_time()),
+            config.min_event_gap_in_minutes
+        );
+    }
+    if cli.phase {
+        println!(
+            "{}",
+            timestamps.current_phase(clock.now_date_time()).get_glyph()
+        );
+        return Ok(());
+    }
+    let only: Vec<String> = match &cli.until {
+        Some(event) => vec![event.clone()],
+        None => cli.only.clone(),
+    };
+    if !only.is_empty() {
+        if let Some(unknown) = only.iter().find(|name| {
+            !timestamps
+                .events
+                .iter()
+                .any(|event| name.eq_ignore_ascii_case(event.get_name()))
+        }) {
+            return Err(InputError::UnknownEvent(unknown.clone()).into());
+        }
+        timestamps.retain_events(&only);
+    }
+
+    if cli.json {
+        println!("{}", serde_json::to_string(&Schedule::from(&timestamps))?);
+        return Ok(());
+    }
+
+    if cli.env {
+        println!("{}", to_env(&timestamps));
+        return Ok(());
+    }
+
+    if let Some(path) = &cli.ical {
+        let date = timestamps.date.unwrap_or(cli.zone().now().date());
+        std::fs::write(path, to_ical(&timestamps, date))
+            .map_err(|e| FileError::Unwritable(path.clone(), e))?;
+        return Ok(());
+    }
+
+    if cli.oneshot || cli.at.is_some() {
+        let now = match cli.at {
+            Some(at) => preview_moment(&timestamps, at, cli.zone()),
+            None => clock.now_date_time(),
+        };
+        if let Some(upcomming) = timestamps.get_upcomming_timestamp_on(now) {
+            println!(
+                "{} {}",
+                timestamps.get_label(upcomming),
+                format_countdown(timestamps.get_time_until(upcomming, now))
+            );
+        }
+        return Ok(());
+    }
+
+    if !cli.no_summary && !cli.jsonl {
+        print_summary(&timestamps);
+    }
+    if cli.verbose {
+        print_derivations(&timestamps, measured_sunrise, bounds);
+    }
+    if cli.speak_summary && !timestamps.events.is_empty() {
+        // Without a speech command there is nothing to fall back to, the summary is printed.
+        speak(&spoken_summary(&timestamps));
+    }
+
+    let display_settings = DisplaySettings {
+        all: cli.all,
+        quiet: cli.quiet,
+        interval: std::time::Duration::from_secs(cli.interval),
+        watch: cli
+            .watch
+            .map(|minutes| std::time::Duration::from_secs(minutes * 60)),
+        format: cli.format.clone(),
+        exit_after_alert: cli.exit_after_alert,
+        bar: cli.bar,
+        since: cli.since,
+        until: cli.until.is_some(),
+        summary: !cli.no_summary && !cli.jsonl,
+        clock: cli.clock,
+        jsonl: cli.jsonl,
+    };
+    let reschedule = || {
+        let today = cli.zone().now().date();
+        let (measured_sunrise, sunset) = match sun_times_for_location(&cli, today) {
+            Some(Ok((sunrise, sunset))) => (sunrise, Some(sunset)),
+            Some(Err(e)) => {
+                eprintln!("\nWarning: {:?}, keeping yesterday's schedule", e);
+                (measured_sunrise, sunset)
+            }
+            // A sunrise given by hand applies to every day.
+            None => (measured_sunrise, sunset),
+        };
+        // A pinned date is kept until that day is over, later days get their own schedule.
+        let mut timestamps = TimestampsBuilder::from_config(&config)
+            .sunrise(clamp_sunrise(measured_sunrise, bounds))
+            .measured_sunrise(measured_sunrise)
+            .sunset(sunset)
+            .date(date.filter(|date| *date >= today))
+            .zone(cli.zone())
+            .build();
+        if !only.is_empty() {
+            timestamps.retain_events(&only);
+        }
+        timestamps
+    };
+    #[cfg(feature = "serve")]
+    if let Some(port) = cli.serve {
+        let server = std::sync::Arc::new(serve::bind(port)?);
+        let served = std::sync::Arc::new(Mutex::new(timestamps.clone()));
+        let handle = {
+            let (server, served, clock) = (server.clone(), served.clone(), SystemClock(cli.zone()));
+            std::thread::spawn(move || serve::run(&server, &served, &clock))
+        };
+        countdown_next_events(
+            timestamps,
+            &reschedule,
+            &clock,
+            &alert_leads,
+            alert_settings,
+            display_settings,
+            Some(&served),
+        );
+        // --until and --exit-after-alert end the countdown, the server goes with it.
+        server.unblock();
+        let _ = handle.join();
+        return Ok(());
+    }
+
+    countdown_next_events(
+        timestamps,
+        &reschedule,
+        &clock,
+        &alert_leads,
+        alert_settings,
+        display_settings,
+        None,
     );
 
-    countdown_next_events(timestamps);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sunrise_is_read_in_24_and_12_hour_time() {
+        let expected = NaiveTime::from_hms_opt(6, 42, 0).unwrap();
+        assert_eq!(parse_time("06:42").unwrap(), expected);
+        assert_eq!(parse_time("6:42").unwrap(), expected);
+        assert_eq!(parse_time("6:42 AM").unwrap(), expected);
+        assert_eq!(parse_time("6:42am").unwrap(), expected);
+        assert_eq!(
+            parse_time("6:42 PM").unwrap(),
+            NaiveTime::from_hms_opt(18, 42, 0).unwrap()
+        );
+        assert_eq!(
+            parse_time("6:42:30").unwrap(),
+            NaiveTime::from_hms_opt(6, 42, 30).unwrap()
+        );
+        assert!(matches!(
+            parse_time("6:75"),
+            Err(InputError::TimeOutOfRange(_))
+        ));
+        assert!(matches!(
+            parse_time("nope"),
+            Err(InputError::UnparseableTime(_))
+        ));
+    }
+
+    #[test]
+    fn ical_has_one_event_per_scheduled_event() {
+        let date = NaiveDate::from_ymd_opt(2026, 6, 10).unwrap();
+        let timestamps = TimestampsBuilder::new()
+            .sunrise(NaiveTime::from_hms_opt(8, 40, 0).unwrap())
+            .date(Some(date))
+            .build();
+        let ical = to_ical(&timestamps, date);
+        assert!(ical.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ical.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(
+            ical.matches("BEGIN:VEVENT\r\n").count(),
+            timestamps.events.len()
+        );
+        assert_eq!(
+            ical.matches("END:VEVENT\r\n").count(),
+            timestamps.events.len()
+        );
+        assert!(ical.contains("DTSTART:20260610T082500\r\n"));
+        // Bed time past midnight lands on the next day.
+        assert!(ical.contains("DTSTART:20260611T001000\r\n"));
+    }
+
+    #[test]
+    fn alerts_fire_once_per_lead_even_when_the_exact_second_is_skipped() {
+        let leads = [chrono::Duration::minutes(10), chrono::Duration::minutes(2)];
+        let mut alerted = Vec::new();
+        let mut tick =
+            |seconds| due_alert(&mut alerted, &leads, chrono::Duration::seconds(seconds));
+        assert_eq!(tick(601), None);
+        // 600 is skipped.
+        assert_eq!(tick(599), Some(0));
+        assert_eq!(tick(598), None);
+        assert_eq!(tick(121), None);
+        assert_eq!(tick(100), Some(1));
+        assert_eq!(tick(0), None);
+        // Past the event the countdown wraps to tomorrow's and the leads arm again.
+        assert_eq!(tick(86399), None);
+        assert_eq!(tick(300), Some(0));
+    }
+
+    #[test]
+    fn leads_reached_by_one_tick_alert_once_for_the_latest() {
+        let leads = [chrono::Duration::minutes(10), chrono::Duration::minutes(2)];
+        let mut alerted = Vec::new();
+        let mut tick =
+            |seconds| due_alert(&mut alerted, &leads, chrono::Duration::seconds(seconds));
+        assert_eq!(tick(700), None);
+        assert_eq!(tick(60), Some(1));
+        assert_eq!(tick(59), None);
+    }
+
+    // The only test touching PROMPTY_SUNRISE, so the others never see it set.
+    #[test]
+    fn sunrise_from_the_environment_yields_to_an_argument() {
+        let at = |hour, minute| NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+        std::env::set_var(SUNRISE_ENV, "   ");
+        assert_eq!(env_sunrise(), None);
+        std::env::set_var(SUNRISE_ENV, "06:42");
+        assert_eq!(env_sunrise().as_deref(), Some("06:42"));
+        let (sunrise, sunset, date) = gather_input(&Cli::parse_from(["prompty"])).unwrap();
+        assert_eq!((sunrise, sunset, date), (at(6, 42), None, None));
+        let (sunrise, _, _) = gather_input(&Cli::parse_from(["prompty", "7:10"])).unwrap();
+        assert_eq!(sunrise, at(7, 10));
+        std::env::set_var(SUNRISE_ENV, "6:75");
+        assert!(matches!(
+            gather_input(&Cli::parse_from(["prompty"])),
+            Err(InputError::TimeOutOfRange(_))
+        ));
+        std::env::remove_var(SUNRISE_ENV);
+    }
+
+    #[test]
+    fn lines_are_cut_at_display_columns() {
+        assert_eq!(fit_to_width("abcdef", 4), "abcd\x1b[0m");
+        assert_eq!(fit_to_width("abc", 4), "abc");
+        // Two columns each, the third would only half fit.
+        assert_eq!(fit_to_width("🌙🌙🌙", 5), "🌙🌙\x1b[0m");
+        assert_eq!(
+            fit_to_width("\x1b[1mbold\x1b[0m text", 6),
+            "\x1b[1mbold\x1b[0m t\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn file_errors_name_the_file() {
+        let error = FileError::Unwritable(
+            PathBuf::from("/nonexistent/x.log"),
+            std::io::Error::from(std::io::ErrorKind::NotFound),
+        );
+        assert_eq!(
+            error.to_string(),
+            "Could not write /nonexistent/x.log: entity not found"
+        );
+        assert_eq!(format!("{:?}", error), error.to_string());
+    }
+
+    #[test]
+    fn usage_is_only_shown_for_a_bad_sunrise() {
+        assert!(InputError::UnparseableTime(String::from("nope"))
+            .to_string()
+            .ends_with(USAGE));
+        assert!(InputError::WrongArgumentCount.to_string().ends_with(USAGE));
+        assert!(!InputError::UnknownEvent(String::from("tea"))
+            .to_string()
+            .contains(USAGE));
+        assert!(
+            !InputError::OutOfOrder(vec![String::from("lunch before wake")])
+                .to_string()
+                .contains(USAGE)
+        );
+    }
+
+    #[test]
+    fn out_of_range_times_name_the_part_that_is_off() {
+        assert!(matches!(
+            parse_time("6:75"),
+            Err(InputError::TimeOutOfRange(_))
+        ));
+        assert_eq!(
+            out_of_range_part("6:75").as_deref(),
+            Some("the minute 75 is not within 0-59")
+        );
+        assert_eq!(
+            out_of_range_part("25:00").as_deref(),
+            Some("the hour 25 is not within 0-23")
+        );
+        assert_eq!(out_of_range_part("6:42"), None);
+        assert!(matches!(
+            parse_time("nope"),
+            Err(InputError::UnparseableTime(_))
+        ));
+    }
+
+    #[test]
+    fn dated_sunrise_keeps_the_error_of_its_time() {
+        assert!(matches!(
+            parse_sunrise("2026-06-01 6:75"),
+            Err(InputError::TimeOutOfRange(time)) if time == "6:75"
+        ));
+        assert!(matches!(
+            parse_sunrise("2026-13-01 6:45"),
+            Err(InputError::UnparseableTime(_))
+        ));
+        let (sunrise, date) = parse_sunrise("2026-06-01 6:45").unwrap();
+        assert_eq!(sunrise, NaiveTime::from_hms_opt(6, 45, 0).unwrap());
+        assert_eq!(date, NaiveDate::from_ymd_opt(2026, 6, 1));
+    }
+
+    #[test]
+    fn preview_of_a_dated_sunrise_happens_on_that_day() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 20).unwrap();
+        let timestamps = TimestampsBuilder::new()
+            .sunrise(NaiveTime::from_hms_opt(6, 42, 0).unwrap())
+            .date(Some(date))
+            .build();
+        let at = NaiveTime::from_hms_opt(19, 30, 0).unwrap();
+        let now = preview_moment(&timestamps, at, Zone::default());
+        assert_eq!(now, date.and_time(at));
+        let upcomming = timestamps.get_upcomming_timestamp_on(now).unwrap();
+        assert_eq!(upcomming.get_name(), "screens");
+        assert_eq!(
+            timestamps.get_time_until(upcomming, now),
+            chrono::Duration::minutes(102)
+        );
+    }
 }
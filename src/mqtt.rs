@@ -0,0 +1,69 @@
+use prompty::{EventId, TimestampType};
+use rumqttc::{Client, MqttOptions, QoS};
+use serde::Serialize;
+
+const DEFAULT_PORT: u16 = 1883;
+
+#[derive(Serialize)]
+struct Payload {
+    event: String,
+    fires_at: String,
+}
+
+pub struct Publisher {
+    client: Client,
+    topic: String,
+    last_event: Option<EventId>,
+}
+
+impl Publisher {
+    pub fn connect(broker: &str, topic: &str) -> Result<Publisher, String> {
+        let address = broker.trim_start_matches("mqtt://");
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse()
+                    .map_err(|_| format!("Invalid MQTT broker port in '{}'", broker))?,
+            ),
+            None => (address, DEFAULT_PORT),
+        };
+        let mut options = MqttOptions::new("prompty", host, port);
+        options.set_keep_alive(std::time::Duration::from_secs(30));
+        let (client, mut connection) = Client::new(options, 10);
+        // Polling the connection drives the network IO and reconnects after failures.
+        std::thread::spawn(move || {
+            for notification in connection.iter() {
+                if let Err(e) = notification {
+                    log::warn!("MQTT broker unreachable: {}", e);
+                    std::thread::sleep(std::time::Duration::from_secs(5));
+                }
+            }
+        });
+        Ok(Publisher {
+            client,
+            topic: topic.to_string(),
+            last_event: None,
+        })
+    }
+    pub fn publish(&mut self, event: &TimestampType) {
+        let payload = Payload {
+            event: event.get_name().to_string(),
+            fires_at: event.get_naive_time().format("%H:%M").to_string(),
+        };
+        let Ok(payload) = serde_json::to_vec(&payload) else {
+            return;
+        };
+        if let Err(e) = self
+            .client
+            .try_publish(&self.topic, QoS::AtLeastOnce, false, payload)
+        {
+            log::warn!("Could not queue MQTT message: {}", e);
+        }
+        self.last_event = Some(event.get_id());
+    }
+    pub fn publish_if_changed(&mut self, event: &TimestampType) {
+        if self.last_event != Some(event.get_id()) {
+            self.publish(event);
+        }
+    }
+}
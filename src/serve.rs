@@ -0,0 +1,83 @@
+use std::sync::Mutex;
+
+use prompty::{Clock, Timestamps};
+use tiny_http::{Header, Response, Server};
+
+use crate::Schedule;
+
+pub fn bind(port: u16) -> Result<Server, String> {
+    Server::http(("127.0.0.1", port))
+        .map_err(|e| format!("Could not serve on port {}: {}", port, e))
+}
+
+fn json_response(body: String) -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string(body)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap())
+}
+
+// Answers from the schedule the countdown keeps up to date, until the server is unblocked.
+pub fn run(server: &Server, served: &Mutex<Timestamps>, clock: &dyn Clock) {
+    for request in server.incoming_requests() {
+        let timestamps = served.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let response = match request.url() {
+            "/schedule" => json_response(
+                serde_json::to_string(&Schedule::from(&timestamps)).unwrap_or_default(),
+            ),
+            "/next" => {
+                let now = clock.now_date_time();
+                match timestamps.get_upcomming_timestamp_on(now) {
+                    Some(upcomming) => json_response(
+                        serde_json::json!({
+                            "event": upcomming.get_name(),
+                            "time": upcomming.get_naive_time().format("%H:%M").to_string(),
+                            "in_seconds": timestamps.get_time_until(upcomming, now).num_seconds(),
+                        })
+                        .to_string(),
+                    ),
+                    None => Response::from_string("No events").with_status_code(404),
+                }
+            }
+            _ => Response::from_string("Not found").with_status_code(404),
+        };
+        let _ = request.respond(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prompty::{FixedClock, TimestampsBuilder};
+    use std::io::{Read, Write};
+
+    fn get(server: &Server, path: &str) -> String {
+        let port = server.server_addr().to_ip().unwrap().port();
+        let mut stream = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+        write!(stream, "GET {} HTTP/1.0\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn serves_the_live_schedule_until_unblocked() {
+        let schedule = |sunrise| {
+            TimestampsBuilder::new()
+                .sunrise(chrono::NaiveTime::from_hms_opt(sunrise, 0, 0).unwrap())
+                .build()
+        };
+        let server = std::sync::Arc::new(bind(0).unwrap());
+        let served = std::sync::Arc::new(Mutex::new(schedule(6)));
+        let handle = {
+            let (server, served) = (server.clone(), served.clone());
+            let clock = FixedClock(chrono::NaiveTime::from_hms_opt(3, 0, 0).unwrap());
+            std::thread::spawn(move || run(&server, &served, &clock))
+        };
+        let next = get(&server, "/next");
+        assert!(next.contains(r#""event":"wake""#));
+        assert!(next.contains(r#""time":"05:45""#));
+        *served.lock().unwrap() = schedule(7);
+        assert!(get(&server, "/next").contains(r#""time":"06:45""#));
+        server.unblock();
+        handle.join().unwrap();
+    }
+}
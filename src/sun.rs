@@ -0,0 +1,149 @@
+use chrono::{Datelike, NaiveDate, NaiveTime};
+
+use crate::Zone;
+
+fn julian_century(julian_day: f64) -> f64 {
+    (julian_day - 2451545.0) / 36525.0
+}
+
+fn sun_geometric_mean_longitude(t: f64) -> f64 {
+    (280.46646 + t * (36000.76983 + t * 0.0003032)).rem_euclid(360.0)
+}
+
+fn sun_geometric_mean_anomaly(t: f64) -> f64 {
+    357.52911 + t * (35999.05029 - 0.0001537 * t)
+}
+
+fn earth_orbit_eccentricity(t: f64) -> f64 {
+    0.016708634 - t * (0.000042037 + 0.0000001267 * t)
+}
+
+fn sun_equation_of_center(t: f64) -> f64 {
+    let m = sun_geometric_mean_anomaly(t).to_radians();
+    m.sin() * (1.914602 - t * (0.004817 + 0.000014 * t))
+        + (2.0 * m).sin() * (0.019993 - 0.000101 * t)
+        + (3.0 * m).sin() * 0.000289
+}
+
+fn sun_apparent_longitude(t: f64) -> f64 {
+    let true_longitude = sun_geometric_mean_longitude(t) + sun_equation_of_center(t);
+    let omega = 125.04 - 1934.136 * t;
+    true_longitude - 0.00569 - 0.00478 * omega.to_radians().sin()
+}
+
+fn obliquity_correction(t: f64) -> f64 {
+    let seconds = 21.448 - t * (46.815 + t * (0.00059 - t * 0.001813));
+    let mean_obliquity = 23.0 + (26.0 + seconds / 60.0) / 60.0;
+    let omega = 125.04 - 1934.136 * t;
+    mean_obliquity + 0.00256 * omega.to_radians().cos()
+}
+
+fn sun_declination(t: f64) -> f64 {
+    (obliquity_correction(t).to_radians().sin() * sun_apparent_longitude(t).to_radians().sin())
+        .asin()
+        .to_degrees()
+}
+
+fn equation_of_time(t: f64) -> f64 {
+    let epsilon = obliquity_correction(t).to_radians();
+    let l0 = sun_geometric_mean_longitude(t).to_radians();
+    let e = earth_orbit_eccentricity(t);
+    let m = sun_geometric_mean_anomaly(t).to_radians();
+    let y = (epsilon / 2.0).tan().powi(2);
+    let e_time = y * (2.0 * l0).sin() - 2.0 * e * m.sin()
+        + 4.0 * e * y * m.sin() * (2.0 * l0).cos()
+        - 0.5 * y * y * (4.0 * l0).sin()
+        - 1.25 * e * e * (2.0 * m).sin();
+    e_time.to_degrees() * 4.0
+}
+
+fn sunrise_hour_angle(lat: f64, declination: f64) -> f64 {
+    let lat = lat.to_radians();
+    let declination = declination.to_radians();
+    // Clamped so polar day/night still yield a time instead of NaN.
+    (90.833_f64.to_radians().cos() / (lat.cos() * declination.cos())
+        - lat.tan() * declination.tan())
+    .clamp(-1.0, 1.0)
+    .acos()
+    .to_degrees()
+}
+
+fn sun_event_utc_in_minutes(julian_day: f64, lat: f64, lon: f64, rising: bool) -> f64 {
+    let t = julian_century(julian_day);
+    let hour_angle = sunrise_hour_angle(lat, sun_declination(t));
+    let hour_angle = if rising { hour_angle } else { -hour_angle };
+    720.0 - 4.0 * (lon + hour_angle) - equation_of_time(t)
+}
+
+fn compute_sun_event(lat: f64, lon: f64, date: NaiveDate, zone: Zone, rising: bool) -> NaiveTime {
+    let julian_day = date.num_days_from_ce() as f64 + 1721424.5;
+    let first_guess = sun_event_utc_in_minutes(julian_day, lat, lon, rising);
+    let minutes = sun_event_utc_in_minutes(julian_day + first_guess / 1440.0, lat, lon, rising);
+    let event_utc = date.and_hms_opt(0, 0, 0).unwrap().and_utc()
+        + chrono::Duration::seconds((minutes * 60.0).round() as i64);
+    zone.from_utc(event_utc).time()
+}
+
+pub fn compute_sunrise(lat: f64, lon: f64, date: NaiveDate, zone: Zone) -> NaiveTime {
+    compute_sun_event(lat, lon, date, zone, true)
+}
+
+pub fn compute_sunset(lat: f64, lon: f64, date: NaiveDate, zone: Zone) -> NaiveTime {
+    compute_sun_event(lat, lon, date, zone, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONDON: (f64, f64) = (51.5072, -0.1276);
+    const QUITO: (f64, f64) = (-0.2298, -78.525);
+
+    // Published times are rounded to the minute, refraction varies a little more than that.
+    fn assert_near(computed: NaiveTime, expected: &str) {
+        let expected = NaiveTime::parse_from_str(expected, "%H:%M").unwrap();
+        let off = computed.signed_duration_since(expected).num_seconds().abs();
+        assert!(
+            off <= 120,
+            "{} is not within 2 minutes of {}",
+            computed,
+            expected
+        );
+    }
+
+    fn sun_times(
+        (lat, lon): (f64, f64),
+        date: (i32, u32, u32),
+        zone: chrono_tz::Tz,
+    ) -> (NaiveTime, NaiveTime) {
+        let date = NaiveDate::from_ymd_opt(date.0, date.1, date.2).unwrap();
+        (
+            compute_sunrise(lat, lon, date, Zone(Some(zone))),
+            compute_sunset(lat, lon, date, Zone(Some(zone))),
+        )
+    }
+
+    #[test]
+    fn london_on_the_summer_solstice() {
+        let (sunrise, sunset) = sun_times(LONDON, (2026, 6, 21), chrono_tz::Europe::London);
+        assert_near(sunrise, "04:43");
+        assert_near(sunset, "21:21");
+    }
+
+    #[test]
+    fn london_on_the_winter_solstice() {
+        let (sunrise, sunset) = sun_times(LONDON, (2026, 12, 21), chrono_tz::Europe::London);
+        assert_near(sunrise, "08:04");
+        assert_near(sunset, "15:53");
+    }
+
+    #[test]
+    fn days_at_the_equator_barely_change_over_the_year() {
+        let (sunrise, sunset) = sun_times(QUITO, (2026, 3, 20), chrono_tz::America::Guayaquil);
+        assert_near(sunrise, "06:18");
+        assert_near(sunset, "18:25");
+        let (sunrise, sunset) = sun_times(QUITO, (2026, 6, 21), chrono_tz::America::Guayaquil);
+        assert_near(sunrise, "06:13");
+        assert_near(sunset, "18:19");
+    }
+}